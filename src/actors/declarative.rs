@@ -0,0 +1,349 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, RwLock};
+use std::time::{Duration, SystemTime};
+
+use schemars::schema::RootSchema;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use tracing::{info, warn};
+
+use super::ConnectorActor;
+use crate::dto::KeyMapping;
+use crate::input_schema::{InputSchema, SchemaErrors};
+use crate::json_path;
+use crate::template::Template;
+
+/// How often the actors directory is checked for changes
+const RELOAD_INTERVAL: Duration = Duration::from_secs(2);
+
+static REGISTRY: LazyLock<RwLock<HashMap<String, Arc<DeclarativeActor>>>> =
+    LazyLock::new(Default::default);
+
+/// An actor described in a YAML or JSON file instead of code
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ActorDefinition {
+    /// Name used in `POST /{actor_type}` and `GET /actors/{actor_type}`
+    pub actor_type: String,
+    /// Apify actor ID, or `username/actor-name`
+    pub actor_name: String,
+    /// JSON Schema of the config accepted by the actor
+    pub input_schema: Value,
+    /// Values of the config fields that are not given
+    #[serde(default)]
+    pub defaults: Map<String, Value>,
+    /// Apify input built from the config: a string that is a single `{path}` placeholder
+    /// is replaced by the config value (dropped if missing), other placeholders are
+    /// rendered as text. Without a template, the config is sent as is.
+    pub body_template: Option<Value>,
+    /// Key mapping suited to the actor's output
+    #[serde(default)]
+    pub key_mapping: Vec<KeyMapping>,
+}
+
+/// A loaded actor definition with its compiled input schema
+#[derive(Debug)]
+pub struct DeclarativeActor {
+    pub definition: ActorDefinition,
+    /// File the definition was loaded from
+    pub source: PathBuf,
+    schema: RootSchema,
+    input_schema: InputSchema,
+}
+
+impl DeclarativeActor {
+    /// Parses a definition file (`.json`, `.yaml` or `.yml`) and compiles its schema.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        // YAML goes through JSON values, so enums are written as in API requests
+        let definition: ActorDefinition = match extension(path) {
+            Some("json") => serde_json::from_str(&contents)?,
+            _ => serde_json::from_value(serde_yaml::from_str::<Value>(&contents)?)?,
+        };
+        Self::new(definition, path.to_path_buf())
+    }
+
+    pub fn new(definition: ActorDefinition, source: PathBuf) -> anyhow::Result<Self> {
+        if definition.actor_type.is_empty() || definition.actor_name.is_empty() {
+            anyhow::bail!("actor_type and actor_name cannot be empty");
+        }
+        let input_schema = InputSchema::compile(&definition.input_schema)
+            .map_err(|e| anyhow::anyhow!("Invalid input_schema: {}", e))?;
+        let schema = serde_json::from_value(definition.input_schema.clone())
+            .map_err(|e| anyhow::anyhow!("Invalid input_schema: {}", e))?;
+        if let Some(template) = &definition.body_template {
+            check_template(template)?;
+        }
+        Ok(Self {
+            definition,
+            source,
+            schema,
+            input_schema,
+        })
+    }
+
+    fn check(&self, input: &Value) -> Result<(), SchemaErrors> {
+        let target = format!("{} config", self.definition.actor_type);
+        self.input_schema.check(&target, input)
+    }
+}
+
+impl ConnectorActor for DeclarativeActor {
+    fn actor_type(&self) -> &str {
+        &self.definition.actor_type
+    }
+
+    fn actor_name(&self) -> &str {
+        &self.definition.actor_name
+    }
+
+    fn schema(&self) -> RootSchema {
+        self.schema.clone()
+    }
+
+    /// Applies the defaults to a config and checks it against the input schema,
+    /// reporting every violation.
+    fn parse(&self, config: Value) -> Result<Value, SchemaErrors> {
+        let Value::Object(mut input) = config else {
+            let target = format!("{} config", self.definition.actor_type);
+            return Err(SchemaErrors::input(target, "config must be an object"));
+        };
+        for (key, value) in &self.definition.defaults {
+            input.entry(key.clone()).or_insert_with(|| value.clone());
+        }
+        let input = Value::Object(input);
+        self.check(&input)?;
+        Ok(input)
+    }
+
+    fn validate(&self, config: &Value) -> Result<(), String> {
+        self.check(config).map_err(|e| e.to_string())
+    }
+
+    /// Builds the Apify input from the body template, or returns the config as is.
+    fn to_body(&self, config: &Value) -> Result<HashMap<String, Value>, String> {
+        let body = match &self.definition.body_template {
+            Some(template) => render(template, config).unwrap_or(Value::Null),
+            None => config.clone(),
+        };
+        Ok(match body {
+            Value::Object(map) => map.into_iter().collect(),
+            _ => HashMap::new(),
+        })
+    }
+
+    fn default_key_mapping(&self) -> Vec<KeyMapping> {
+        self.definition.key_mapping.clone()
+    }
+}
+
+/// Returns the path of a `{path}` string, which stands for the value itself.
+fn whole_placeholder(template: &Template, text: &str) -> Option<String> {
+    let mut placeholders = template.placeholders();
+    let placeholder = placeholders.next()?;
+    let whole = placeholders.next().is_none() && text.len() == placeholder.len() + 2;
+    whole.then(|| placeholder.trim().to_string())
+}
+
+fn check_template(template: &Value) -> anyhow::Result<()> {
+    match template {
+        Value::String(text) => Template::parse(text)
+            .map(|_| ())
+            .map_err(|e| anyhow::anyhow!("Invalid body_template: {}", e)),
+        Value::Array(values) => values.iter().try_for_each(check_template),
+        Value::Object(map) => map.values().try_for_each(check_template),
+        _ => Ok(()),
+    }
+}
+
+/// Renders a body template node, `None` when it is a placeholder of a missing value.
+fn render(template: &Value, input: &Value) -> Option<Value> {
+    match template {
+        Value::String(text) => {
+            let Ok(parsed) = Template::parse(text) else {
+                return Some(template.clone());
+            };
+            if let Some(path) = whole_placeholder(&parsed, text) {
+                return json_path::get(input, &path).cloned();
+            }
+            let rendered = parsed.render(|path| {
+                Ok(match json_path::get(input, path.trim()) {
+                    None | Some(Value::Null) => String::new(),
+                    Some(Value::String(s)) => s.clone(),
+                    Some(other) => other.to_string(),
+                })
+            });
+            Some(Value::String(rendered.unwrap_or_default()))
+        }
+        Value::Array(values) => Some(Value::Array(
+            values.iter().filter_map(|v| render(v, input)).collect(),
+        )),
+        Value::Object(map) => Some(Value::Object(
+            map.iter()
+                .filter_map(|(k, v)| render(v, input).map(|v| (k.clone(), v)))
+                .collect(),
+        )),
+        other => Some(other.clone()),
+    }
+}
+
+fn extension(path: &Path) -> Option<&str> {
+    path.extension().and_then(|e| e.to_str())
+}
+
+/// Loads every `.json`, `.yaml` and `.yml` file of a directory. Files that fail to load,
+/// or whose actor type is already taken, are reported and skipped.
+pub fn load_dir(dir: &Path) -> anyhow::Result<(Vec<DeclarativeActor>, Vec<String>)> {
+    let (actors, failed) = load_files(&definition_files(dir)?);
+    let errors = failed
+        .into_iter()
+        .map(|(path, error)| format!("{}: {}", path.display(), error))
+        .collect();
+    Ok((actors, errors))
+}
+
+/// Loads definition files, returning the actors and the files that failed with why
+fn load_files(files: &[PathBuf]) -> (Vec<DeclarativeActor>, Vec<(PathBuf, String)>) {
+    let reserved = super::typed_actor_types();
+    let mut actors: Vec<DeclarativeActor> = Vec::new();
+    let mut failed = Vec::new();
+
+    for path in files {
+        match DeclarativeActor::load(path) {
+            Ok(actor) => {
+                let actor_type = actor.definition.actor_type.as_str();
+                if reserved.contains(&actor_type)
+                    || actors.iter().any(|a| a.definition.actor_type == actor_type)
+                {
+                    let error = format!("actor type '{}' is already defined", actor_type);
+                    failed.push((path.clone(), error));
+                } else {
+                    actors.push(actor);
+                }
+            }
+            Err(e) => failed.push((path.clone(), format!("{:#}", e))),
+        }
+    }
+    (actors, failed)
+}
+
+fn definition_files(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| matches!(extension(path), Some("json" | "yaml" | "yml")))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Replaces the registered declarative actors.
+pub fn install(actors: Vec<DeclarativeActor>) {
+    let actors = actors
+        .into_iter()
+        .map(|actor| (actor.definition.actor_type.clone(), Arc::new(actor)))
+        .collect();
+    *REGISTRY.write().unwrap_or_else(|e| e.into_inner()) = actors;
+}
+
+pub fn get(actor_type: &str) -> Option<Arc<DeclarativeActor>> {
+    REGISTRY
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(actor_type)
+        .cloned()
+}
+
+/// Registered declarative actors, sorted by type
+pub fn list() -> Vec<Arc<DeclarativeActor>> {
+    let mut actors: Vec<_> = REGISTRY
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .values()
+        .cloned()
+        .collect();
+    actors.sort_by(|a, b| a.definition.actor_type.cmp(&b.definition.actor_type));
+    actors
+}
+
+/// Loads the actors of a directory into the registry, logging the files that failed.
+/// A file that fails to load, e.g. while it is being edited, keeps the actor it defined
+/// before; only a deleted file removes its actor. Returns the number of actors registered.
+pub fn reload(dir: &Path) -> anyhow::Result<usize> {
+    let (actors, failed) = load_files(&definition_files(dir)?);
+    let mut registry: HashMap<String, Arc<DeclarativeActor>> = actors
+        .into_iter()
+        .map(|actor| (actor.definition.actor_type.clone(), Arc::new(actor)))
+        .collect();
+    let previous = list();
+    for (path, error) in failed {
+        warn!(path = %path.display(), error, "Skipped actor definition");
+        let kept = previous.iter().find(|actor| actor.source == path);
+        if let Some(actor) = kept
+            && !registry.contains_key(&actor.definition.actor_type)
+        {
+            warn!(
+                actor_type = actor.definition.actor_type,
+                "Keeping the last loaded definition"
+            );
+            registry.insert(actor.definition.actor_type.clone(), actor.clone());
+        }
+    }
+    let count = registry.len();
+    *REGISTRY.write().unwrap_or_else(|e| e.into_inner()) = registry;
+    Ok(count)
+}
+
+/// Loads the actors of `$ACTORS_DIR`, if set, and reloads them when the files change.
+/// Returns the number of actors loaded.
+pub fn init_from_env() -> anyhow::Result<Option<usize>> {
+    let Some(dir) = std::env::var_os("ACTORS_DIR").map(PathBuf::from) else {
+        return Ok(None);
+    };
+    let count = reload(&dir)?;
+    watch(dir);
+    Ok(Some(count))
+}
+
+/// Reloads the registry whenever a definition file is added, changed or removed.
+/// File system access runs on the blocking thread pool.
+pub fn watch(dir: PathBuf) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut last = blocking(&dir, fingerprint).await;
+        loop {
+            tokio::time::sleep(RELOAD_INTERVAL).await;
+            let current = blocking(&dir, fingerprint).await;
+            if current == last {
+                continue;
+            }
+            last = current;
+            match blocking(&dir, reload).await {
+                Ok(count) => info!(count, "Reloaded declarative actors"),
+                Err(e) => warn!(error = %e, "Failed to reload declarative actors"),
+            }
+        }
+    })
+}
+
+/// Runs `f` on the directory on the blocking thread pool
+async fn blocking<T: Send + 'static>(dir: &Path, f: fn(&Path) -> T) -> T {
+    let dir = dir.to_path_buf();
+    tokio::task::spawn_blocking(move || f(&dir))
+        .await
+        .expect("blocking reload task panicked")
+}
+
+/// Sizes and modification times of the definition files, to detect changes
+fn fingerprint(dir: &Path) -> Vec<(PathBuf, Option<(u64, SystemTime)>)> {
+    definition_files(dir)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|path| {
+            let stat = fs::metadata(&path)
+                .and_then(|m| Ok((m.len(), m.modified()?)))
+                .ok();
+            (path, stat)
+        })
+        .collect()
+}
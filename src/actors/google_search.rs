@@ -1,7 +1,18 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use super::ActorMetadata;
+use serde_json::json;
+
+use super::TypedActor;
+use crate::dto::{DataKind, KeyMapping};
+
+/// Joins the title and description of each organic result of a search page
+const ORGANIC_RESULTS_TEXT: &str = r#"
+if type_of(value) != "array" { return (); }
+value
+    .map(|r| (r.title ?? "") + "\n" + (r.description ?? ""))
+    .reduce(|text, r| if text == () { r } else { text + "\n\n" + r })
+"#;
 
 /// Configuration for the Google Search Scraper actor.
 /// Scrapes Google search results for given queries.
@@ -28,16 +39,13 @@ fn default_language() -> String {
     "en".to_string()
 }
 
-impl GoogleSearchConfig {
-    pub fn metadata() -> ActorMetadata {
-        ActorMetadata {
-            actor_type: "google_search",
-            actor_name: "apify/google-search-scraper",
-            schema: schemars::schema_for!(GoogleSearchConfig),
-        }
-    }
+register_actor!(GoogleSearchConfig);
 
-    pub fn validate(&self) -> Result<(), String> {
+impl TypedActor for GoogleSearchConfig {
+    const ACTOR_TYPE: &'static str = "google_search";
+    const ACTOR_NAME: &'static str = "apify/google-search-scraper";
+
+    fn validate(&self) -> Result<(), String> {
         if self.queries.is_empty() {
             return Err("queries cannot be empty".into());
         }
@@ -56,4 +64,21 @@ impl GoogleSearchConfig {
         }
         Ok(())
     }
+
+    /// Items are search result pages, dated when extracted
+    fn default_key_mapping() -> Vec<KeyMapping> {
+        vec![
+            KeyMapping::new(&["organicResults"], "content", DataKind::String)
+                .with_transform(ORGANIC_RESULTS_TEXT),
+            KeyMapping::new(
+                &["date"],
+                "date",
+                DataKind::Date {
+                    format: "%Y-%m-%d".into(),
+                },
+            )
+            .with_default(json!("now")),
+            KeyMapping::new(&["url"], "url", DataKind::String),
+        ]
+    }
 }
@@ -1,7 +1,8 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use super::ActorMetadata;
+use super::TypedActor;
+use crate::dto::{DataKind, KeyMapping};
 
 /// Configuration for the Instagram Scraper actor.
 /// Scrapes Instagram profiles and posts.
@@ -25,16 +26,13 @@ fn default_max_posts() -> u32 {
     50
 }
 
-impl InstagramScraperConfig {
-    pub fn metadata() -> ActorMetadata {
-        ActorMetadata {
-            actor_type: "instagram",
-            actor_name: "apify/instagram-scraper",
-            schema: schemars::schema_for!(InstagramScraperConfig),
-        }
-    }
+register_actor!(InstagramScraperConfig);
+
+impl TypedActor for InstagramScraperConfig {
+    const ACTOR_TYPE: &'static str = "instagram";
+    const ACTOR_NAME: &'static str = "apify/instagram-scraper";
 
-    pub fn validate(&self) -> Result<(), String> {
+    fn validate(&self) -> Result<(), String> {
         if self.usernames.is_empty() {
             return Err("usernames cannot be empty".into());
         }
@@ -51,4 +49,20 @@ impl InstagramScraperConfig {
         }
         Ok(())
     }
+
+    fn default_key_mapping() -> Vec<KeyMapping> {
+        vec![
+            KeyMapping::new(&["id"], "id", DataKind::String),
+            KeyMapping::new(&["caption"], "content", DataKind::String),
+            KeyMapping::new(
+                &["timestamp"],
+                "date",
+                DataKind::Date {
+                    format: "%Y-%m-%dT%H:%M:%S%.fZ".into(),
+                },
+            ),
+            KeyMapping::new(&["ownerUsername"], "author", DataKind::String),
+            KeyMapping::new(&["url"], "url", DataKind::String),
+        ]
+    }
 }
@@ -1,121 +1,390 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::marker::PhantomData;
+use std::sync::{Arc, LazyLock, Mutex};
+
+use schemars::schema::RootSchema;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::dto::{JobState, KeyMapping, StateMapping};
+use crate::input_schema::{InputSchema, SchemaErrors};
+use crate::json_path::{self, Segment};
+use crate::mapping_utils::apply_state_mapping;
+use crate::validation::{FieldError, ValidationErrors};
+
+/// Registers a [`TypedActor`] config, making it available by its `ACTOR_TYPE`.
+macro_rules! register_actor {
+    ($config:ty) => {
+        inventory::submit! {
+            $crate::actors::Registration {
+                actor_type: <$config as $crate::actors::TypedActor>::ACTOR_TYPE,
+                new: $crate::actors::Typed::<$config>::handle,
+            }
+        }
+    };
+}
+
+pub mod declarative;
 mod google_search;
 mod instagram;
+pub mod remote;
 mod tripadvisor;
 mod web_scraper;
 
+pub use declarative::{ActorDefinition, DeclarativeActor};
 pub use google_search::GoogleSearchConfig;
 pub use instagram::InstagramScraperConfig;
 pub use tripadvisor::TripAdvisorConfig;
 pub use web_scraper::WebScraperConfig;
 
-use schemars::schema::RootSchema;
-use serde::{Deserialize, Serialize};
-use serde_json::Value;
-use std::collections::HashMap;
-
 /// Metadata about an available actor/scraper with JSON Schema
 #[derive(Debug, Clone, Serialize)]
 pub struct ActorMetadata {
-    pub actor_type: &'static str,
-    pub actor_name: &'static str,
+    pub actor_type: String,
+    pub actor_name: String,
     pub schema: RootSchema,
+    /// Key mapping suited to the actor's output
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub default_key_mapping: Vec<KeyMapping>,
+}
+
+/// An actor the connector can run. Configs are handled as JSON in the actor's
+/// canonical form, as returned by [`ConnectorActor::parse`].
+pub trait ConnectorActor: Send + Sync {
+    /// Name used in `POST /{actor_type}` and `GET /actors/{actor_type}`
+    fn actor_type(&self) -> &str;
+    /// Apify actor ID, or `username/actor-name`
+    fn actor_name(&self) -> &str;
+    /// JSON Schema of the config
+    fn schema(&self) -> RootSchema;
+    /// Checks a config against the JSON Schema and parses it, applying defaults
+    fn parse(&self, config: Value) -> Result<Value, SchemaErrors>;
+    /// Checks the values of a parsed config
+    fn validate(&self, config: &Value) -> Result<(), String>;
+    /// Builds the Apify input from a parsed config
+    fn to_body(&self, config: &Value) -> Result<HashMap<String, Value>, String>;
+    /// Key mapping suited to the actor's output
+    fn default_key_mapping(&self) -> Vec<KeyMapping> {
+        Vec::new()
+    }
+    /// The default key mapping, with each given mapping replacing the default of the
+    /// same target or added after them. A mapping with no `from` removes the default.
+    fn key_mapping(&self, overrides: &[KeyMapping]) -> Vec<KeyMapping> {
+        let mut key_mapping = self.default_key_mapping();
+        for mapping in overrides {
+            match key_mapping.iter().position(|m| m.to == mapping.to) {
+                Some(i) if mapping.from.is_empty() => {
+                    key_mapping.remove(i);
+                }
+                Some(i) => key_mapping[i] = mapping.clone(),
+                None => key_mapping.push(mapping.clone()),
+            }
+        }
+        key_mapping
+    }
+    /// Reshapes a raw dataset item before key mapping
+    fn normalize_output(&self, item: Value) -> Value {
+        item
+    }
+
+    fn metadata(&self) -> ActorMetadata {
+        ActorMetadata {
+            actor_type: self.actor_type().to_string(),
+            actor_name: self.actor_name().to_string(),
+            schema: self.schema(),
+            default_key_mapping: self.default_key_mapping(),
+        }
+    }
+}
+
+/// A compiled-in actor: its config struct is the input, with a JSON Schema derived by
+/// schemars. Register it with `register_actor!(Config);` next to its definition.
+pub trait TypedActor: DeserializeOwned + Serialize + JsonSchema + 'static {
+    const ACTOR_TYPE: &'static str;
+    const ACTOR_NAME: &'static str;
+
+    fn validate(&self) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Converts the config to the Apify input, by default its serialized fields
+    fn to_body(&self) -> Result<HashMap<String, Value>, serde_json::Error> {
+        match serde_json::to_value(self)? {
+            Value::Object(map) => Ok(map.into_iter().collect()),
+            _ => Ok(HashMap::new()),
+        }
+    }
+
+    fn default_key_mapping() -> Vec<KeyMapping> {
+        Vec::new()
+    }
+
+    fn normalize_output(item: Value) -> Value {
+        item
+    }
+}
+
+/// Compiled schemas of the typed actors, by actor type
+static TYPED_SCHEMAS: LazyLock<Mutex<HashMap<&'static str, Arc<InputSchema>>>> =
+    LazyLock::new(Default::default);
+
+/// [`ConnectorActor`] implementation of a [`TypedActor`]
+pub struct Typed<C>(PhantomData<fn() -> C>);
+
+impl<C: TypedActor> Typed<C> {
+    pub fn handle() -> Arc<dyn ConnectorActor> {
+        Arc::new(Self(PhantomData))
+    }
+
+    fn input_schema() -> Arc<InputSchema> {
+        let mut schemas = TYPED_SCHEMAS.lock().unwrap_or_else(|e| e.into_inner());
+        schemas
+            .entry(C::ACTOR_TYPE)
+            .or_insert_with(|| {
+                let schema = serde_json::to_value(schemars::schema_for!(C))
+                    .expect("schemars schemas serialize to JSON");
+                Arc::new(InputSchema::compile(&schema).expect("schemars schemas are valid"))
+            })
+            .clone()
+    }
+
+    fn config(config: &Value) -> Result<C, String> {
+        serde_json::from_value(config.clone()).map_err(|e| e.to_string())
+    }
+}
+
+impl<C: TypedActor> ConnectorActor for Typed<C> {
+    fn actor_type(&self) -> &str {
+        C::ACTOR_TYPE
+    }
+
+    fn actor_name(&self) -> &str {
+        C::ACTOR_NAME
+    }
+
+    fn schema(&self) -> RootSchema {
+        schemars::schema_for!(C)
+    }
+
+    fn parse(&self, config: Value) -> Result<Value, SchemaErrors> {
+        let target = format!("{} config", C::ACTOR_TYPE);
+        Self::input_schema().check(&target, &config)?;
+        let config: C = serde_json::from_value(config)
+            .map_err(|e| SchemaErrors::input(&target, e.to_string()))?;
+        serde_json::to_value(&config).map_err(|e| SchemaErrors::input(&target, e.to_string()))
+    }
+
+    fn validate(&self, config: &Value) -> Result<(), String> {
+        Self::config(config)?.validate()
+    }
+
+    fn to_body(&self, config: &Value) -> Result<HashMap<String, Value>, String> {
+        Self::config(config)?.to_body().map_err(|e| e.to_string())
+    }
+
+    fn default_key_mapping(&self) -> Vec<KeyMapping> {
+        C::default_key_mapping()
+    }
+
+    fn normalize_output(&self, item: Value) -> Value {
+        C::normalize_output(item)
+    }
+}
+
+/// A compiled-in actor, collected at link time by `register_actor!`
+pub struct Registration {
+    pub actor_type: &'static str,
+    pub new: fn() -> Arc<dyn ConnectorActor>,
+}
+
+inventory::collect!(Registration);
+
+/// Types of the compiled-in actors, sorted
+pub fn typed_actor_types() -> Vec<&'static str> {
+    let mut types: Vec<&str> = inventory::iter::<Registration>
+        .into_iter()
+        .map(|r| r.actor_type)
+        .collect();
+    types.sort();
+    types
+}
+
+/// Returns an actor by type, compiled-in actors first
+pub fn find_actor(actor_type: &str) -> Option<Arc<dyn ConnectorActor>> {
+    inventory::iter::<Registration>
+        .into_iter()
+        .find(|r| r.actor_type == actor_type)
+        .map(|r| (r.new)())
+        .or_else(|| declarative::get(actor_type).map(|actor| actor as Arc<dyn ConnectorActor>))
 }
 
-/// Returns metadata for all available actors
+/// Returns metadata for all available actors, compiled-in ones first
 pub fn list_available_actors() -> Vec<ActorMetadata> {
-    vec![
-        WebScraperConfig::metadata(),
-        GoogleSearchConfig::metadata(),
-        InstagramScraperConfig::metadata(),
-        TripAdvisorConfig::metadata(),
-    ]
+    let mut actors: Vec<ActorMetadata> = typed_actor_types()
+        .into_iter()
+        .filter_map(get_actor_metadata)
+        .collect();
+    actors.extend(declarative::list().iter().map(|actor| actor.metadata()));
+    actors
 }
 
 /// Returns metadata for a specific actor type
 pub fn get_actor_metadata(actor_type: &str) -> Option<ActorMetadata> {
-    match actor_type {
-        "web_scraper" => Some(WebScraperConfig::metadata()),
-        "google_search" => Some(GoogleSearchConfig::metadata()),
-        "instagram" => Some(InstagramScraperConfig::metadata()),
-        "tripadvisor" => Some(TripAdvisorConfig::metadata()),
-        _ => None,
-    }
+    find_actor(actor_type).map(|actor| actor.metadata())
+}
+
+/// A parsed config of an actor
+#[derive(Clone)]
+pub struct ActorConfig {
+    actor: Arc<dyn ConnectorActor>,
+    config: Value,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
-pub enum ActorConfig {
-    WebScraper(WebScraperConfig),
-    GoogleSearch(GoogleSearchConfig),
-    Instagram(InstagramScraperConfig),
-    TripAdvisor(TripAdvisorConfig),
+impl fmt::Debug for ActorConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ActorConfig")
+            .field("actor_type", &self.actor_type())
+            .field("config", &self.config)
+            .finish()
+    }
 }
 
 impl ActorConfig {
-    /// Parse an ActorConfig from actor_type path parameter and config JSON
-    pub fn from_type_and_config(actor_type: &str, config: Value) -> Result<Self, String> {
-        match actor_type {
-            "web_scraper" => serde_json::from_value(config)
-                .map(ActorConfig::WebScraper)
-                .map_err(|e| format!("Invalid web_scraper config: {}", e)),
-            "google_search" => serde_json::from_value(config)
-                .map(ActorConfig::GoogleSearch)
-                .map_err(|e| format!("Invalid google_search config: {}", e)),
-            "instagram" => serde_json::from_value(config)
-                .map(ActorConfig::Instagram)
-                .map_err(|e| format!("Invalid instagram config: {}", e)),
-            "tripadvisor" => serde_json::from_value(config)
-                .map(ActorConfig::TripAdvisor)
-                .map_err(|e| format!("Invalid tripadvisor config: {}", e)),
-            _ => Err(format!("Unknown actor type: {}", actor_type)),
-        }
+    /// Parse an ActorConfig from actor_type path parameter and config JSON.
+    /// A config that does not match the actor's schema fails with [`SchemaErrors`].
+    pub fn from_type_and_config(actor_type: &str, config: Value) -> anyhow::Result<Self> {
+        let actor = find_actor(actor_type)
+            .ok_or_else(|| anyhow::anyhow!("Unknown actor type: {}", actor_type))?;
+        let config = actor.parse(config)?;
+        Ok(Self { actor, config })
     }
 
     /// Returns the actor_type string for this config
-    pub fn actor_type(&self) -> &'static str {
-        match self {
-            Self::WebScraper(_) => "web_scraper",
-            Self::GoogleSearch(_) => "google_search",
-            Self::Instagram(_) => "instagram",
-            Self::TripAdvisor(_) => "tripadvisor",
-        }
+    pub fn actor_type(&self) -> &str {
+        self.actor.actor_type()
     }
 
     /// Returns the Apify actor identifier for this configuration
-    pub fn actor_name(&self) -> &'static str {
-        match self {
-            Self::WebScraper(_) => "apify/web-scraper",
-            Self::GoogleSearch(_) => "apify/google-search-scraper",
-            Self::Instagram(_) => "apify/instagram-scraper",
-            Self::TripAdvisor(_) => "Hvp4YfFGyLM635Q2F",
-        }
+    pub fn actor_name(&self) -> &str {
+        self.actor.actor_name()
+    }
+
+    /// The config, in the actor's canonical form
+    pub fn config(&self) -> &Value {
+        &self.config
     }
 
     /// Validates the configuration
     pub fn validate(&self) -> Result<(), String> {
-        match self {
-            Self::WebScraper(c) => c.validate(),
-            Self::GoogleSearch(c) => c.validate(),
-            Self::Instagram(c) => c.validate(),
-            Self::TripAdvisor(c) => c.validate(),
-        }
+        self.actor.validate(&self.config)
     }
 
-    /// Converts the typed config to a HashMap for the API call
-    pub fn to_body(&self) -> Result<HashMap<String, Value>, serde_json::Error> {
-        match self {
-            Self::TripAdvisor(c) => c.to_apify_body(),
-            _ => {
-                let value = match self {
-                    Self::WebScraper(c) => serde_json::to_value(c)?,
-                    Self::GoogleSearch(c) => serde_json::to_value(c)?,
-                    Self::Instagram(c) => serde_json::to_value(c)?,
-                    Self::TripAdvisor(_) => unreachable!(),
-                };
-                match value {
-                    Value::Object(map) => Ok(map.into_iter().collect()),
-                    _ => Ok(HashMap::new()),
-                }
+    /// Returns the metadata of this actor type
+    pub fn metadata(&self) -> ActorMetadata {
+        self.actor.metadata()
+    }
+
+    /// The actor's default key mapping, with each given mapping replacing the default
+    /// of the same target or added after them, or removing it when it has no `from`
+    pub fn key_mapping(&self, overrides: &[KeyMapping]) -> Vec<KeyMapping> {
+        self.actor.key_mapping(overrides)
+    }
+
+    /// Reshapes a raw dataset item of this actor before key mapping
+    pub fn normalize_output(&self, item: Value) -> Value {
+        self.actor.normalize_output(item)
+    }
+
+    /// Builds the Apify input with state values placed into it.
+    ///
+    /// A mapping whose `to` starts with a field of the config schema goes into the config,
+    /// which is then parsed and validated again, so state cannot give a field the wrong
+    /// type or an invalid value, and the input is rebuilt from it. A mapping whose `to`
+    /// starts with another key of the built input, such as `startUrls[0].userData.since`,
+    /// goes into the input. Any other `to` is rejected; mappings without `to` only keep a
+    /// cursor in the state.
+    pub fn body_with_state(
+        &self,
+        state: &JobState,
+        state_mapping: Option<&Vec<StateMapping>>,
+    ) -> Result<HashMap<String, Value>, ValidationErrors> {
+        let invalid = |e: String| {
+            ValidationErrors::field(
+                "state_mapping",
+                format!("State mapping produced an invalid config: {}", e),
+            )
+        };
+        let body = self
+            .to_body()
+            .map_err(|e| ValidationErrors::field("actor_config", e))?;
+        let Some(mapping) = state_mapping else {
+            return Ok(body);
+        };
+
+        let metadata = self.metadata();
+        let fields = metadata
+            .schema
+            .schema
+            .object
+            .as_ref()
+            .map(|object| &object.properties);
+        let mut config_mapping = Vec::new();
+        let mut body_mapping = Vec::new();
+        let mut unknown = Vec::new();
+        for (i, m) in mapping.iter().enumerate() {
+            let Some(to) = &m.to else {
+                continue;
+            };
+            // Paths that don't parse are reported by `validate_mappings`
+            let key = match json_path::parse(to).ok().and_then(|s| s.into_iter().next()) {
+                Some(Segment::Key(key)) => key,
+                Some(Segment::Index(_)) => to.clone(),
+                None => continue,
+            };
+            if fields.is_some_and(|fields| fields.contains_key(&key)) {
+                config_mapping.push(m.clone());
+            } else if body.contains_key(&key) {
+                body_mapping.push(m.clone());
+            } else {
+                unknown.push(FieldError {
+                    field: format!("state_mapping[{}].to", i),
+                    message: format!(
+                        "'{}' is not a field of the {} config",
+                        key, metadata.actor_type
+                    ),
+                });
             }
         }
+        if !unknown.is_empty() {
+            return Err(ValidationErrors(unknown));
+        }
+
+        let mut config = match &self.config {
+            Value::Object(map) => map.clone().into_iter().collect(),
+            _ => HashMap::new(),
+        };
+        apply_state_mapping(&mut config, state, Some(&config_mapping))
+            .map_err(|e| invalid(e.to_string()))?;
+        let config = Self::from_type_and_config(
+            self.actor_type(),
+            Value::Object(config.into_iter().collect()),
+        )
+        .map_err(|e| invalid(e.to_string()))?;
+        config.validate().map_err(invalid)?;
+
+        let mut body = config
+            .to_body()
+            .map_err(|e| ValidationErrors::field("actor_config", e))?;
+        apply_state_mapping(&mut body, state, Some(&body_mapping))
+            .map_err(|e| invalid(e.to_string()))?;
+        Ok(body)
+    }
+
+    /// Converts the config to a HashMap for the API call
+    pub fn to_body(&self) -> Result<HashMap<String, Value>, String> {
+        self.actor.to_body(&self.config)
     }
 }
@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+use schemars::schema::RootSchema;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tracing::debug;
+
+use super::ActorMetadata;
+use crate::client::ApiFyClient;
+
+/// How long a fetched actor is reused before asking Apify again
+const CACHE_TTL: Duration = Duration::from_secs(10 * 60);
+const CACHE_SIZE: usize = 256;
+
+static CACHE: LazyLock<Mutex<ActorCache>> =
+    LazyLock::new(|| Mutex::new(ActorCache::new(CACHE_TTL, CACHE_SIZE)));
+
+/// An Apify store actor, with the input schema of its latest build
+#[derive(Debug)]
+pub struct RemoteActor {
+    pub id: String,
+    /// `username/actor-name`
+    pub name: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub input_schema: Option<Value>,
+}
+
+impl RemoteActor {
+    /// The actor in the shape of `GET /actors/{actor_type}`, with an empty schema when
+    /// the latest build has none
+    pub fn metadata(&self) -> anyhow::Result<ActorMetadata> {
+        let schema: RootSchema = match &self.input_schema {
+            Some(schema) => serde_json::from_value(schema.clone())
+                .map_err(|e| anyhow::anyhow!("Invalid input schema of '{}': {}", self.name, e))?,
+            None => RootSchema::default(),
+        };
+        Ok(ActorMetadata {
+            actor_type: self.id.clone(),
+            actor_name: self.name.clone(),
+            schema,
+            default_key_mapping: Vec::new(),
+        })
+    }
+}
+
+/// Fetched actors with their fetch time, by digest of the actor ID and token, so private
+/// actors are only served to the token that can read them
+#[derive(Debug)]
+pub struct ActorCache {
+    ttl: Duration,
+    capacity: usize,
+    entries: HashMap<[u8; 32], (Instant, Arc<RemoteActor>)>,
+}
+
+impl ActorCache {
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            ttl,
+            capacity,
+            entries: HashMap::new(),
+        }
+    }
+
+    /// The actor fetched with this token, while it is fresh
+    pub fn get(&self, actor_id: &str, token: Option<&str>) -> Option<Arc<RemoteActor>> {
+        self.entries
+            .get(&cache_key(actor_id, token))
+            .filter(|(fetched, _)| fetched.elapsed() < self.ttl)
+            .map(|(_, actor)| actor.clone())
+    }
+
+    /// Adds an actor. When the cache is full, expired entries are dropped first, and
+    /// everything if none has expired.
+    pub fn insert(&mut self, actor_id: &str, token: Option<&str>, actor: Arc<RemoteActor>) {
+        if self.entries.len() >= self.capacity {
+            let ttl = self.ttl;
+            self.entries
+                .retain(|_, (fetched, _)| fetched.elapsed() < ttl);
+            if self.entries.len() >= self.capacity {
+                self.entries.clear();
+            }
+        }
+        self.entries
+            .insert(cache_key(actor_id, token), (Instant::now(), actor));
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+fn cache_key(actor_id: &str, token: Option<&str>) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(actor_id.as_bytes());
+    hasher.update([0]);
+    hasher.update(token.unwrap_or_default().as_bytes());
+    hasher.finalize().into()
+}
+
+/// Returns an actor and its input schema, from the cache while it is fresh.
+/// Without a token, only public actors can be read.
+///
+/// Concurrent misses for the same actor each fetch it from Apify and the last one is
+/// cached; that costs a few extra requests, which is simpler than coordinating them.
+pub async fn get(actor_id: &str, token: Option<&str>) -> anyhow::Result<Arc<RemoteActor>> {
+    if let Some(actor) = cache().get(actor_id, token) {
+        return Ok(actor);
+    }
+
+    debug!(actor_id, "Fetching remote actor");
+    let client = match token {
+        Some(token) => ApiFyClient::new(token),
+        None => ApiFyClient::anonymous(),
+    };
+    let details = client.get_actor(actor_id).await?;
+    let input_schema = client.get_input_schema(&details).await?;
+    let actor = Arc::new(RemoteActor {
+        id: details.id,
+        name: format!("{}/{}", details.username, details.name),
+        title: details.title,
+        description: details.description,
+        input_schema,
+    });
+
+    cache().insert(actor_id, token, actor.clone());
+    Ok(actor)
+}
+
+fn cache() -> std::sync::MutexGuard<'static, ActorCache> {
+    CACHE.lock().unwrap_or_else(|e| e.into_inner())
+}
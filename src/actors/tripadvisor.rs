@@ -4,7 +4,8 @@ use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-use super::ActorMetadata;
+use super::TypedActor;
+use crate::dto::{DataKind, KeyMapping};
 
 /// Configuration for the TripAdvisor Reviews Scraper actor.
 /// Scrapes reviews from TripAdvisor attraction, restaurant, or hotel pages.
@@ -35,16 +36,13 @@ fn default_review_languages() -> Vec<String> {
     vec!["ALL_REVIEW_LANGUAGES".to_string()]
 }
 
-impl TripAdvisorConfig {
-    pub fn metadata() -> ActorMetadata {
-        ActorMetadata {
-            actor_type: "tripadvisor",
-            actor_name: "Hvp4YfFGyLM635Q2F",
-            schema: schemars::schema_for!(TripAdvisorConfig),
-        }
-    }
+register_actor!(TripAdvisorConfig);
+
+impl TypedActor for TripAdvisorConfig {
+    const ACTOR_TYPE: &'static str = "tripadvisor";
+    const ACTOR_NAME: &'static str = "Hvp4YfFGyLM635Q2F";
 
-    pub fn validate(&self) -> Result<(), String> {
+    fn validate(&self) -> Result<(), String> {
         if self.url.is_empty() {
             return Err("url cannot be empty".into());
         }
@@ -57,8 +55,24 @@ impl TripAdvisorConfig {
         Ok(())
     }
 
+    fn default_key_mapping() -> Vec<KeyMapping> {
+        vec![
+            KeyMapping::new(&["id"], "id", DataKind::String),
+            KeyMapping::new(&["text"], "content", DataKind::String),
+            KeyMapping::new(
+                &["publishedDate"],
+                "date",
+                DataKind::Date {
+                    format: "%Y-%m-%d".into(),
+                },
+            ),
+            KeyMapping::new(&["title"], "title", DataKind::String),
+            KeyMapping::new(&["url"], "url", DataKind::String),
+        ]
+    }
+
     /// Converts to Apify API format (transforms single url to startUrls array)
-    pub fn to_apify_body(&self) -> Result<HashMap<String, Value>, serde_json::Error> {
+    fn to_body(&self) -> Result<HashMap<String, Value>, serde_json::Error> {
         let mut body = HashMap::new();
 
         // Transform single url to startUrls array format
@@ -1,7 +1,10 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use super::ActorMetadata;
+use serde_json::json;
+
+use super::TypedActor;
+use crate::dto::{DataKind, KeyMapping};
 
 /// Configuration for the Apify Web Scraper actor.
 /// Scrapes web pages starting from given URLs.
@@ -24,16 +27,13 @@ fn default_max_pages() -> u32 {
     100
 }
 
-impl WebScraperConfig {
-    pub fn metadata() -> ActorMetadata {
-        ActorMetadata {
-            actor_type: "web_scraper",
-            actor_name: "apify/web-scraper",
-            schema: schemars::schema_for!(WebScraperConfig),
-        }
-    }
+register_actor!(WebScraperConfig);
 
-    pub fn validate(&self) -> Result<(), String> {
+impl TypedActor for WebScraperConfig {
+    const ACTOR_TYPE: &'static str = "web_scraper";
+    const ACTOR_NAME: &'static str = "apify/web-scraper";
+
+    fn validate(&self) -> Result<(), String> {
         if self.start_urls.is_empty() {
             return Err("start_urls cannot be empty".into());
         }
@@ -47,4 +47,21 @@ impl WebScraperConfig {
         }
         Ok(())
     }
+
+    /// Pages have no publication date, they are dated when extracted
+    fn default_key_mapping() -> Vec<KeyMapping> {
+        vec![
+            KeyMapping::new(&["text", "content"], "content", DataKind::String),
+            KeyMapping::new(
+                &["date"],
+                "date",
+                DataKind::Date {
+                    format: "%Y-%m-%d".into(),
+                },
+            )
+            .with_default(json!("now")),
+            KeyMapping::new(&["title"], "title", DataKind::String),
+            KeyMapping::new(&["url"], "url", DataKind::String),
+        ]
+    }
 }
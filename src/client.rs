@@ -7,7 +7,7 @@ use serde_json::Value;
 use tracing::{debug, info, instrument};
 
 use crate::actors::ActorConfig;
-use crate::dto::{Data, Root, RunId};
+use crate::dto::{ActorDetails, ApiResponse, BuildDetails, Data, Root, RunId};
 use crate::metrics::{record_api_duration, record_api_request};
 
 const APIFY_API_BASE: &str = "https://api.apify.com/v2";
@@ -46,6 +46,13 @@ impl ApiFyClient {
         Self { client }
     }
 
+    /// Client without a token, enough to read public actors and their builds
+    pub fn anonymous() -> Self {
+        Self {
+            client: reqwest::Client::new(),
+        }
+    }
+
     #[instrument(skip(self, body), fields(actor = %actor))]
     pub async fn start_job(
         &self,
@@ -79,6 +86,57 @@ impl ApiFyClient {
         self.start_job(config.actor_name(), &body).await
     }
 
+    #[instrument(skip(self), fields(actor = %actor))]
+    pub async fn get_actor(&self, actor: &str) -> anyhow::Result<ActorDetails> {
+        let start = Instant::now();
+        record_api_request("get_actor");
+
+        let url = format!("{}/acts/{}", APIFY_API_BASE, actor_path(actor));
+        let resp: ApiResponse<ActorDetails> = self
+            .client
+            .get(&url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        record_api_duration("get_actor", start.elapsed().as_secs_f64());
+        Ok(resp.data)
+    }
+
+    #[instrument(skip(self), fields(build_id = %build_id))]
+    pub async fn get_build(&self, build_id: &str) -> anyhow::Result<BuildDetails> {
+        let start = Instant::now();
+        record_api_request("get_build");
+
+        let url = format!("{}/actor-builds/{}", APIFY_API_BASE, build_id);
+        let resp: ApiResponse<BuildDetails> = self
+            .client
+            .get(&url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        record_api_duration("get_build", start.elapsed().as_secs_f64());
+        Ok(resp.data)
+    }
+
+    /// Input schema of the latest build of an actor, `None` when it has none
+    pub async fn get_input_schema(&self, actor: &ActorDetails) -> anyhow::Result<Option<Value>> {
+        let Some(build_id) = actor
+            .tagged_builds
+            .get("latest")
+            .and_then(|build| build.build_id.as_deref())
+        else {
+            debug!("Actor has no latest build");
+            return Ok(None);
+        };
+        self.get_build(build_id).await?.schema()
+    }
+
     #[instrument(skip(self), fields(dataset_id = %dataset_id))]
     pub async fn download_results(
         &self,
@@ -115,6 +173,11 @@ impl ApiFyClient {
     }
 }
 
+/// Actor ID or name as a path segment, `username/actor-name` becoming `username~actor-name`
+fn actor_path(actor: &str) -> String {
+    actor.replace('/', "~")
+}
+
 pub enum State {
     Running,
     Succeeded,
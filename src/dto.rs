@@ -5,6 +5,8 @@ use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
 use crate::client::DatasetId;
+use crate::input_schema::SchemaViolation;
+use crate::validation::FieldError;
 
 #[derive(Debug, Deserialize)]
 pub struct RunId(pub String);
@@ -107,6 +109,57 @@ pub struct Stats {
 pub struct Root {
     pub data: Data,
 }
+
+/// `data` envelope of the Apify API responses
+#[derive(Debug, Deserialize)]
+pub struct ApiResponse<T> {
+    pub data: T,
+}
+
+/// Actor as returned by `GET /acts/{actorId}`
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActorDetails {
+    pub id: String,
+    pub name: String,
+    pub username: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    /// Builds by tag, e.g. `latest`
+    #[serde(default)]
+    pub tagged_builds: HashMap<String, TaggedBuild>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaggedBuild {
+    pub build_id: Option<String>,
+}
+
+/// Build as returned by `GET /actor-builds/{buildId}`
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BuildDetails {
+    /// JSON encoded input schema
+    pub input_schema: Option<String>,
+    /// Contents of `.actor/actor.json`, with the input schema under `input`
+    pub actor_definition: Option<Value>,
+}
+
+impl BuildDetails {
+    /// The input schema of the build, if it has one
+    pub fn schema(&self) -> anyhow::Result<Option<Value>> {
+        if let Some(schema) = &self.input_schema {
+            return Ok(Some(serde_json::from_str(schema)?));
+        }
+        Ok(self
+            .actor_definition
+            .as_ref()
+            .and_then(|definition| definition.get("input"))
+            .filter(|input| input.is_object())
+            .cloned())
+    }
+}
    
 
 #[derive(Serialize, Clone, Debug)]
@@ -118,10 +171,55 @@ pub struct ExportItem {
     pub metadata: HashMap<String, String>,
 }
 
+/// Why a single raw item could not be turned into an ExportItem
+#[derive(Serialize, Clone, Debug)]
+pub struct ItemError {
+    /// Position of the item in the downloaded dataset
+    pub index: usize,
+    pub reason: String,
+}
+
+/// Summary of what happened to the raw items during extraction
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct ExtractionReport {
+    /// Raw items received from the dataset
+    pub seen: usize,
+    /// Items exported in `result`
+    pub kept: usize,
+    /// Items that failed extraction
+    pub dropped: usize,
+    /// Items skipped by the `filter` expression
+    pub filtered: usize,
+    /// Items skipped because they were already seen
+    pub duplicates: usize,
+    /// Sample of per-item errors (capped, see `extraction::MAX_ERROR_SAMPLES`)
+    pub errors: Vec<ItemError>,
+}
+
 #[derive(Serialize, Debug)]
 pub struct Response {
+    /// Json encoded state, kept for clients that predate `state_object`
     pub state: String,
+    /// The same state as a JSON object
+    pub state_object: JobState,
+    /// Version of the saved state, for jobs with a `job_name`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state_version: Option<u64>,
     pub result: Vec<ExportItem>,
+    pub report: ExtractionReport,
+}
+
+impl Response {
+    pub fn new(state: JobState, result: Vec<ExportItem>, report: ExtractionReport) -> Self {
+        let encoded = Value::Object(state.clone().into_iter().collect()).to_string();
+        Self {
+            state: encoded,
+            state_object: state,
+            state_version: None,
+            result,
+            report,
+        }
+    }
 }
 
 mod jackson {
@@ -155,27 +253,249 @@ mod jackson {
     // }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub enum DataKind {
     Date { format: String },
     String,
+    /// HTML fragment cleaned to single-line text
+    HtmlText,
+    /// HTML fragment cleaned to text, keeping paragraph and line breaks
+    HtmlParagraphs,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct KeyMapping {
-    pub from: String,
+    /// Source field, or ordered list of candidate fields (first non-null value wins)
+    #[serde(deserialize_with = "one_or_many")]
+    pub from: Vec<String>,
     pub to: String,
     pub kind: DataKind,
+    /// Value used when none of the source fields is present.
+    /// For `date`, `"now"` stands for the extraction time.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default: Option<Value>,
+    /// Rhai expression applied to the source value before conversion.
+    /// `value` holds the source value (unit if missing) and `item` the whole raw item.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transform: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+impl KeyMapping {
+    pub fn new(from: &[&str], to: &str, kind: DataKind) -> Self {
+        Self {
+            from: from.iter().map(|s| s.to_string()).collect(),
+            to: to.to_string(),
+            kind,
+            default: None,
+            transform: None,
+        }
+    }
+
+    pub fn with_default(mut self, default: Value) -> Self {
+        self.default = Some(default);
+        self
+    }
+
+    pub fn with_transform(mut self, transform: &str) -> Self {
+        self.transform = Some(transform.to_string());
+        self
+    }
+}
+
+/// Accepts either a single string or a list of strings
+fn one_or_many<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(s) => vec![s],
+        OneOrMany::Many(v) => v,
+    })
+}
+
+#[derive(Debug, Clone, Deserialize)]
 pub struct StateMapping {
     /// copy from this field
     pub from: String,
-    /// to this field
-    pub to: String,
+    /// to this path of the actor input, e.g. `search.dateFrom` or `startUrls[0].userData.since`.
+    /// Without it (or with `null`), the value is only kept in the state, as a cursor.
+    #[serde(default)]
+    pub to: Option<String>,
     /// at the end map to this value
-    pub update: String,
+    pub update: StateUpdate,
+    /// how the value is combined with what the input already has at `to`
+    #[serde(default)]
+    pub merge: MergeStrategy,
+}
+
+/// How a state value is placed into the actor input
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+pub enum MergeStrategy {
+    /// Overwrites the target
+    #[default]
+    Replace,
+    /// Adds the value (or each element of an array value) to the target array
+    Append,
+    /// Merges objects key by key, recursively; other values replace the target
+    DeepMerge,
+}
+
+/// How a state key is updated after a run.
+///
+/// Written as `{"literal": ...}`, `{"expr": "..."}`, `{"template": "..."}` or
+/// `{"keep": true}`. A plain string is still accepted: `$...` is an expression and
+/// anything else a literal string.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StateUpdate {
+    /// Stores this JSON value
+    Literal(Value),
+    /// Stores the result of a Rhai expression; `()` leaves the value untouched
+    Expr(String),
+    /// Renders a text template such as `{start_date:%Y-%m-%d}`
+    Template(String),
+    /// Leaves the value untouched
+    Keep,
+}
+
+impl From<&str> for StateUpdate {
+    fn from(update: &str) -> Self {
+        match update.strip_prefix('$') {
+            Some(script) => Self::Expr(script.to_string()),
+            None => Self::Literal(Value::String(update.to_string())),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for StateUpdate {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "snake_case", deny_unknown_fields)]
+        enum Tagged {
+            Literal(Value),
+            Expr(String),
+            Template(String),
+            Keep(bool),
+        }
+
+        let value = Value::deserialize(deserializer)?;
+        if let Value::String(update) = &value {
+            return Ok(update.as_str().into());
+        }
+        let tagged = serde_json::from_value(value).map_err(|e| {
+            serde::de::Error::custom(format!(
+                "Invalid state update, expected a string or one of literal, expr, template, keep: {}",
+                e
+            ))
+        })?;
+        Ok(match tagged {
+            Tagged::Literal(value) => Self::Literal(value),
+            Tagged::Expr(script) => Self::Expr(script),
+            Tagged::Template(template) => Self::Template(template),
+            Tagged::Keep(true) => Self::Keep,
+            Tagged::Keep(false) => {
+                return Err(serde::de::Error::custom(
+                    "Invalid state update: 'keep' can only be true",
+                ));
+            }
+        })
+    }
+}
+
+/// Fails the job when too many items are dropped during extraction
+#[derive(Debug, Deserialize)]
+pub struct StrictSettings {
+    /// Highest tolerated ratio of dropped items to seen items (0.0 - 1.0)
+    #[serde(default)]
+    pub max_drop_ratio: f64,
+}
+
+/// What identifies an item when removing duplicates
+#[derive(Debug, Deserialize)]
+pub enum DedupeKey {
+    /// `ExportItem.id`; items without an id are never considered duplicates
+    Id,
+    /// Hash of the given raw item fields
+    Fields(Vec<String>),
+    /// Hash of `ExportItem.content`
+    Content,
+}
+
+/// Removes duplicate items within a run and optionally across runs
+#[derive(Debug, Deserialize)]
+pub struct DedupeSettings {
+    pub by: DedupeKey,
+    /// Also skip items seen by previous runs, remembering digests in the job state
+    #[serde(default)]
+    pub across_runs: bool,
+    /// Maximum number of digests remembered across runs (oldest are forgotten first)
+    #[serde(default = "default_max_seen")]
+    pub max_seen: usize,
+}
+
+fn default_max_seen() -> usize {
+    10_000
+}
+
+/// How to generate `ExportItem.id` for items that have no mapped id
+#[derive(Debug, Deserialize)]
+pub enum IdStrategy {
+    /// SHA-256 (hex) of the given raw item fields
+    Hash { fields: Vec<String> },
+    /// UUIDv5 of the given raw item fields (namespace defaults to the URL namespace)
+    UuidV5 {
+        namespace: Option<String>,
+        fields: Vec<String>,
+    },
+    /// Apify run ID followed by the item's index in the dataset
+    RunIndex,
+}
+
+/// Which unmapped raw item fields are copied into metadata
+#[derive(Debug, Default, Deserialize)]
+pub enum PassthroughMode {
+    #[default]
+    All,
+    None,
+    /// Only fields matching one of these glob patterns (`*` and `?` wildcards)
+    Allow(Vec<String>),
+    /// Every field except those matching one of these glob patterns
+    Deny(Vec<String>),
+}
+
+/// Controls the copy of unmapped string fields into metadata
+#[derive(Debug, Default, Deserialize)]
+pub struct PassthroughSettings {
+    #[serde(default)]
+    pub mode: PassthroughMode,
+    /// Copied values longer than this many characters are truncated
+    pub max_length: Option<usize>,
+}
+
+/// Options controlling how raw actor results are turned into ExportItems
+#[derive(Debug, Default, Deserialize)]
+pub struct ExtractionSettings {
+    pub strict: Option<StrictSettings>,
+    /// Rhai expression evaluated on each raw item (`item`) with the job state (`state`).
+    /// Items for which it returns false are skipped.
+    pub filter: Option<String>,
+    pub dedupe: Option<DedupeSettings>,
+    pub id_strategy: Option<IdStrategy>,
+    #[serde(default)]
+    pub passthrough: PassthroughSettings,
+    /// Builds `content` from several fields, e.g. `"{title}\n\n{text}"`.
+    /// Placeholders are nested paths (`{author.name}`, `{tags[0]}`) with an optional
+    /// default after `|` (`{title|Untitled}`); missing values render as empty text.
+    pub content_template: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -183,16 +503,25 @@ pub struct Settings {
     /// Actor configuration (parsed based on path parameter actor_type)
     pub actor_config: Value,
     pub token: String,
+    /// Mappings added to the actor's default key mapping, replacing the defaults
+    /// with the same `to`
+    #[serde(default)]
     pub key_mapping: Vec<KeyMapping>,
     pub state_mapping: Option<Vec<StateMapping>>,
+    #[serde(flatten)]
+    pub extraction: ExtractionSettings,
 }
 
 /// job with all settings and state
 #[derive(Debug, Deserialize)]
 pub struct JobCreation {
     pub settings: Settings,
-    /// Json encoded state
-    pub state: String,
+    /// Name of the job in the state store. Its saved state replaces `state`, and the
+    /// updated state is saved after a successful run.
+    pub job_name: Option<String>,
+    /// State as a JSON object, or JSON encoded in a string
+    #[serde(default, deserialize_with = "state_object")]
+    pub state: JobState,
 }
 
 /// Settings for running an arbitrary Apify actor
@@ -205,12 +534,128 @@ pub struct ArbitraryActorSettings {
     pub token: String,
     pub key_mapping: Vec<KeyMapping>,
     pub state_mapping: Option<Vec<StateMapping>>,
+    #[serde(flatten)]
+    pub extraction: ExtractionSettings,
 }
 
 /// Job request for running an arbitrary Apify actor
 #[derive(Debug, Deserialize)]
 pub struct ArbitraryActorJob {
     pub settings: ArbitraryActorSettings,
-    /// Json encoded state
-    pub state: String,
+    /// Name of the job in the state store. Its saved state replaces `state`, and the
+    /// updated state is saved after a successful run.
+    pub job_name: Option<String>,
+    /// State as a JSON object, or JSON encoded in a string
+    #[serde(default, deserialize_with = "state_object")]
+    pub state: JobState,
+}
+
+/// Request to try mappings against sample data without running an actor
+#[derive(Debug, Deserialize)]
+pub struct PreviewRequest {
+    /// Raw items, as an actor would return them
+    pub items: Option<Vec<Value>>,
+    /// Existing Apify dataset to read the items from instead (requires `token`)
+    pub dataset_id: Option<String>,
+    pub token: Option<String>,
+    pub key_mapping: Vec<KeyMapping>,
+    pub state_mapping: Option<Vec<StateMapping>>,
+    #[serde(flatten)]
+    pub extraction: ExtractionSettings,
+    /// State as a JSON object, or JSON encoded in a string
+    #[serde(default, deserialize_with = "state_object")]
+    pub state: JobState,
+}
+
+/// Request to check mappings and settings without running anything
+#[derive(Debug, Deserialize)]
+pub struct ValidateRequest {
+    pub key_mapping: Vec<KeyMapping>,
+    pub state_mapping: Option<Vec<StateMapping>>,
+    #[serde(flatten)]
+    pub extraction: ExtractionSettings,
+    /// State the state mapping expressions are dry-run against
+    #[serde(default, deserialize_with = "state_object")]
+    pub state: JobState,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ValidateResponse {
+    pub valid: bool,
+}
+
+/// Request to check a typed actor job without running it
+#[derive(Debug, Deserialize)]
+pub struct ActorValidateRequest {
+    pub actor_config: Value,
+    /// Mappings added to the actor's default key mapping, as in a job
+    #[serde(default)]
+    pub key_mapping: Vec<KeyMapping>,
+    pub state_mapping: Option<Vec<StateMapping>>,
+    #[serde(flatten)]
+    pub extraction: ExtractionSettings,
+    /// State placed into the config by the state mapping
+    #[serde(default, deserialize_with = "state_object")]
+    pub state: JobState,
+}
+
+/// Everything found while checking a typed actor job
+#[derive(Debug, Serialize)]
+pub struct ActorValidateResponse {
+    pub valid: bool,
+    /// Apify input the actor would be started with, state applied
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<HashMap<String, Value>>,
+    /// Key mapping the job would use: the actor's defaults with the request's mappings
+    pub key_mapping: Vec<KeyMapping>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FieldError>,
+    /// Violations of the actor's JSON Schema, by JSON pointer into `actor_config`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub violations: Vec<SchemaViolation>,
+}
+
+/// Request to overwrite the stored state of a job
+#[derive(Debug, Deserialize)]
+pub struct PutStateRequest {
+    /// State as a JSON object, or JSON encoded in a string
+    #[serde(deserialize_with = "state_object")]
+    pub state: JobState,
+    /// Version the stored state must still be at (0 when none is stored yet)
+    pub version: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VersionQuery {
+    /// Version the stored state must still be at
+    pub version: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TokenQuery {
+    /// Apify token, needed for private actors only
+    pub token: Option<String>,
+}
+
+/// Job state: named values carried from one run to the next
+pub type JobState = HashMap<String, Value>;
+
+/// Accepts the state as a JSON object or, for compatibility, JSON encoded in a string
+fn state_object<'de, D>(deserializer: D) -> Result<JobState, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum EncodedOrObject {
+        Encoded(String),
+        Object(JobState),
+    }
+
+    match EncodedOrObject::deserialize(deserializer)? {
+        EncodedOrObject::Encoded(s) => serde_json::from_str(&s).map_err(|e| {
+            serde::de::Error::custom(format!("Invalid JSON encoded state: {}", e))
+        }),
+        EncodedOrObject::Object(state) => Ok(state),
+    }
 }
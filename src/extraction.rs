@@ -1,28 +1,378 @@
+use std::borrow::Cow;
 use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 
 use anyhow::Context;
-use chrono::{NaiveDate, TimeZone, Utc};
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use rhai::{Dynamic, Scope, AST};
 use serde_json::Value;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
 
-use crate::dto::{DataKind, ExportItem, KeyMapping};
+use crate::dto::{
+    DataKind, DedupeKey, DedupeSettings, ExportItem, ExtractionReport, ExtractionSettings,
+    IdStrategy, ItemError, JobState, KeyMapping, PassthroughMode, PassthroughSettings,
+};
+use crate::html_text::html_to_text;
+use crate::json_path::{self, Segment};
+use crate::scripting;
+use crate::template::Template;
+
+/// Maximum number of per-item errors kept in the extraction report
+pub const MAX_ERROR_SAMPLES: usize = 20;
+
+/// State key holding the digests of items seen by previous runs
+pub const SEEN_DIGESTS_KEY: &str = "_seen_digests";
+
+/// Items extracted from a dataset along with what was dropped on the way
+#[derive(Debug)]
+pub struct Extraction {
+    pub items: Vec<ExportItem>,
+    pub report: ExtractionReport,
+    /// Digests to remember for the next run, when deduplicating across runs
+    pub seen_digests: Option<Vec<String>>,
+}
+
+/// Job data available to expressions during extraction
+#[derive(Debug, Default)]
+pub struct ExtractionContext {
+    /// State the job started with
+    pub state: JobState,
+    /// Apify run that produced the dataset, used by `IdStrategy::RunIndex`
+    pub run_id: Option<String>,
+}
 
 /// Extracts a Vec<ExportItem> from JSON array data using key mappings.
+///
+/// Items rejected by the filter are skipped, items that cannot be extracted are
+/// dropped, and both are accounted for in the report.
+/// In strict mode, the extraction fails when the drop ratio exceeds the threshold.
 pub fn extract_export_items(
     data: Vec<Value>,
     key_mappings: &[KeyMapping],
-) -> anyhow::Result<Vec<ExportItem>> {
-    let items = data
+    settings: &ExtractionSettings,
+    ctx: &ExtractionContext,
+) -> anyhow::Result<Extraction> {
+    let mapper = Mapper::new(key_mappings, settings)?;
+    let filter = settings
+        .filter
+        .as_deref()
+        .map(scripting::compile)
+        .transpose()
+        .context("Invalid filter")?;
+    let state = scripting::to_dynamic(&serde_json::to_value(&ctx.state)?);
+    let id_generator = settings
+        .id_strategy
+        .as_ref()
+        .map(|strategy| IdGenerator::new(strategy, ctx))
+        .transpose()?;
+    let mut deduper = settings
+        .dedupe
+        .as_ref()
+        .map(|dedupe| Deduper::new(dedupe, &ctx.state));
+
+    let mut items = Vec::with_capacity(data.len());
+    let mut report = ExtractionReport {
+        seen: data.len(),
+        ..Default::default()
+    };
+
+    for (index, item_value) in data.iter().enumerate() {
+        let extracted = match &filter {
+            Some(ast) => match mapper.filter(ast, item_value, &state) {
+                Ok(true) => extract_single_export_item(item_value, &mapper),
+                Ok(false) => {
+                    report.filtered += 1;
+                    continue;
+                }
+                Err(e) => Err(e),
+            },
+            None => extract_single_export_item(item_value, &mapper),
+        };
+
+        match extracted {
+            Ok(mut item) => {
+                if item.id.is_none()
+                    && let Some(id_generator) = &id_generator
+                {
+                    item.id = Some(id_generator.generate(item_value, index));
+                }
+                if let Some(deduper) = &mut deduper
+                    && deduper.is_duplicate(&item, item_value)
+                {
+                    report.duplicates += 1;
+                    continue;
+                }
+                items.push(item);
+            }
+            Err(e) => {
+                report.dropped += 1;
+                if report.errors.len() < MAX_ERROR_SAMPLES {
+                    report.errors.push(ItemError {
+                        index,
+                        reason: format!("{:#}", e),
+                    });
+                }
+            }
+        }
+    }
+    report.kept = items.len();
+
+    if let Some(strict) = &settings.strict {
+        check_drop_ratio(&report, strict.max_drop_ratio)?;
+    }
+
+    Ok(Extraction {
+        items,
+        report,
+        seen_digests: deduper.and_then(Deduper::into_history),
+    })
+}
+
+/// A key mapping with its transform compiled once per extraction
+struct CompiledMapping<'a> {
+    mapping: &'a KeyMapping,
+    transform: Option<Arc<AST>>,
+}
+
+/// Key mappings prepared once and applied to every item of a dataset
+struct Mapper<'a> {
+    mappings: Vec<CompiledMapping<'a>>,
+    passthrough: &'a PassthroughSettings,
+    content_template: Option<Template>,
+    /// Top-level fields read by the content template, kept out of metadata
+    template_keys: Vec<String>,
+}
+
+impl<'a> Mapper<'a> {
+    fn new(
+        key_mappings: &'a [KeyMapping],
+        settings: &'a ExtractionSettings,
+    ) -> anyhow::Result<Self> {
+        let mappings = key_mappings
+            .iter()
+            .map(|mapping| {
+                let transform = mapping
+                    .transform
+                    .as_deref()
+                    .map(scripting::compile)
+                    .transpose()
+                    .with_context(|| format!("Invalid transform for '{}'", mapping.to))?;
+                Ok(CompiledMapping { mapping, transform })
+            })
+            .collect::<anyhow::Result<_>>()?;
+        let content_template = settings
+            .content_template
+            .as_deref()
+            .map(Template::parse)
+            .transpose()
+            .context("Invalid content template")?;
+        let template_keys = content_template
+            .iter()
+            .flat_map(Template::placeholders)
+            .filter_map(|placeholder| {
+                let (path, _) = split_default(placeholder);
+                match json_path::parse(path).ok()?.into_iter().next()? {
+                    Segment::Key(key) => Some(key),
+                    Segment::Index(_) => None,
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            mappings,
+            passthrough: &settings.passthrough,
+            content_template,
+            template_keys,
+        })
+    }
+
+    fn has_transforms(&self) -> bool {
+        self.mappings.iter().any(|m| m.transform.is_some())
+    }
+
+    /// Evaluates a transform. A unit or null result counts as a missing value.
+    fn transform(
+        &self,
+        ast: &AST,
+        value: Option<&Value>,
+        item: &Dynamic,
+    ) -> anyhow::Result<Option<Value>> {
+        let mut scope = Scope::new();
+        scope.push(
+            "value",
+            value.map(scripting::to_dynamic).unwrap_or(Dynamic::UNIT),
+        );
+        scope.push("item", item.clone());
+        let result: Dynamic = scripting::engine()
+            .eval_ast_with_scope(&mut scope, ast)
+            .map_err(|e| anyhow::anyhow!("{}", e))?;
+        match scripting::from_dynamic(&result)? {
+            Value::Null => Ok(None),
+            value => Ok(Some(value)),
+        }
+    }
+
+    /// Evaluates the filter expression for a raw item.
+    fn filter(&self, ast: &AST, item: &Value, state: &Dynamic) -> anyhow::Result<bool> {
+        let mut scope = Scope::new();
+        scope.push("item", scripting::to_dynamic(item));
+        scope.push("state", state.clone());
+        let result: Dynamic = scripting::engine()
+            .eval_ast_with_scope(&mut scope, ast)
+            .map_err(|e| anyhow::anyhow!("Filter failed: {}", e))?;
+        result
+            .as_bool()
+            .map_err(|t| anyhow::anyhow!("Filter must return a bool, got {}", t))
+    }
+}
+
+/// Id strategy resolved once per extraction
+enum IdGenerator<'a> {
+    Hash(&'a [String]),
+    UuidV5(Uuid, &'a [String]),
+    RunIndex(&'a str),
+}
+
+impl<'a> IdGenerator<'a> {
+    fn new(strategy: &'a IdStrategy, ctx: &'a ExtractionContext) -> anyhow::Result<Self> {
+        Ok(match strategy {
+            IdStrategy::Hash { fields } => Self::Hash(fields),
+            IdStrategy::UuidV5 { namespace, fields } => {
+                let namespace = match namespace {
+                    Some(namespace) => Uuid::parse_str(namespace)
+                        .with_context(|| format!("Invalid UUID namespace '{}'", namespace))?,
+                    None => Uuid::NAMESPACE_URL,
+                };
+                Self::UuidV5(namespace, fields)
+            }
+            IdStrategy::RunIndex => Self::RunIndex(
+                ctx.run_id
+                    .as_deref()
+                    .ok_or_else(|| anyhow::anyhow!("RunIndex id strategy requires a run ID"))?,
+            ),
+        })
+    }
+
+    fn generate(&self, raw: &Value, index: usize) -> String {
+        match self {
+            Self::Hash(fields) => {
+                format!("{:x}", Sha256::digest(join_fields(raw, fields).as_bytes()))
+            }
+            Self::UuidV5(namespace, fields) => {
+                Uuid::new_v5(namespace, join_fields(raw, fields).as_bytes()).to_string()
+            }
+            Self::RunIndex(run_id) => format!("{}-{}", run_id, index),
+        }
+    }
+}
+
+/// Remembers item digests to skip duplicates
+struct Deduper<'a> {
+    settings: &'a DedupeSettings,
+    /// Digests from previous runs followed by new ones, oldest first
+    history: Vec<String>,
+    seen: HashSet<String>,
+}
+
+impl<'a> Deduper<'a> {
+    fn new(settings: &'a DedupeSettings, state: &JobState) -> Self {
+        let history: Vec<String> = if settings.across_runs {
+            state
+                .get(SEEN_DIGESTS_KEY)
+                .and_then(|v| serde_json::from_value(v.clone()).ok())
+                .unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+        let seen = history.iter().cloned().collect();
+        Self {
+            settings,
+            history,
+            seen,
+        }
+    }
+
+    /// Returns true when the item was already seen, remembering it otherwise.
+    fn is_duplicate(&mut self, item: &ExportItem, raw: &Value) -> bool {
+        let key = match &self.settings.by {
+            DedupeKey::Id => match &item.id {
+                Some(id) => Cow::Borrowed(id.as_str()),
+                None => return false,
+            },
+            DedupeKey::Fields(fields) => Cow::Owned(join_fields(raw, fields)),
+            DedupeKey::Content => Cow::Borrowed(item.content.as_str()),
+        };
+        let digest = short_digest(&key);
+        if !self.seen.insert(digest.clone()) {
+            return true;
+        }
+        self.history.push(digest);
+        false
+    }
+
+    /// Most recent digests to store in the job state, if deduplicating across runs.
+    fn into_history(mut self) -> Option<Vec<String>> {
+        if !self.settings.across_runs {
+            return None;
+        }
+        let excess = self.history.len().saturating_sub(self.settings.max_seen);
+        self.history.drain(..excess);
+        Some(self.history)
+    }
+}
+
+/// Marks a missing field in a hashing key, so it differs from an empty one
+const MISSING_FIELD: &str = "\u{0}";
+
+/// Joins raw item field values into a single hashing key.
+fn join_fields(raw: &Value, fields: &[String]) -> String {
+    fields
         .iter()
-        .filter_map(|item_value| extract_single_export_item(item_value, key_mappings).ok())
-        .collect();
-    Ok(items)
+        .map(|field| match raw.get(field) {
+            Some(Value::String(s)) => s.clone(),
+            Some(value) => value.to_string(),
+            None => MISSING_FIELD.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\u{1f}")
+}
+
+/// First 16 hex chars of the SHA-256 of `key`, stable across runs and versions.
+fn short_digest(key: &str) -> String {
+    let mut hex = format!("{:x}", Sha256::digest(key.as_bytes()));
+    hex.truncate(16);
+    hex
+}
+
+/// Fails when the share of dropped items is above `max_drop_ratio`. Items removed by
+/// the filter were skipped on purpose and don't count.
+fn check_drop_ratio(report: &ExtractionReport, max_drop_ratio: f64) -> anyhow::Result<()> {
+    let considered = report.seen - report.filtered;
+    if considered == 0 {
+        return Ok(());
+    }
+    let ratio = report.dropped as f64 / considered as f64;
+    if ratio > max_drop_ratio {
+        let first_reason = report
+            .errors
+            .first()
+            .map(|e| e.reason.as_str())
+            .unwrap_or("unknown");
+        anyhow::bail!(
+            "Dropped {} of {} items ({:.1}%), above the strict threshold of {:.1}% (first error: {})",
+            report.dropped,
+            considered,
+            ratio * 100.0,
+            max_drop_ratio * 100.0,
+            first_reason
+        );
+    }
+    Ok(())
 }
 
 /// Extracts a single ExportItem from a JSON object using key mappings.
-fn extract_single_export_item(
-    data: &Value,
-    key_mappings: &[KeyMapping],
-) -> anyhow::Result<ExportItem> {
+fn extract_single_export_item(data: &Value, mapper: &Mapper) -> anyhow::Result<ExportItem> {
     let map = data
         .as_object()
         .ok_or_else(|| anyhow::anyhow!("Item in array is not a JSON object"))?;
@@ -32,41 +382,79 @@ fn extract_single_export_item(
     let mut date = None;
     let mut metadata = HashMap::new();
     let mut mapped_keys = HashSet::new();
+    let item = mapper.has_transforms().then(|| scripting::to_dynamic(data));
 
-    for mapping in key_mappings {
-        if let Some(value) = map.get(&mapping.from) {
-            mapped_keys.insert(&mapping.from);
-
-            match mapping.to.as_str() {
-                "id" => id = value.as_str().map(String::from),
-                "content" => content = value.as_str().map(String::from),
-                "date" => {
-                    if let DataKind::Date { format } = &mapping.kind
-                        && let Some(s) = value.as_str()
-                    {
-                        let parsed_date =
-                            NaiveDate::parse_from_str(s, format).with_context(|| {
-                                format!("Failed to parse date '{}' with format '{}'", s, format)
-                            })?;
-                        date =
-                            Some(Utc.from_utc_datetime(&parsed_date.and_hms_opt(0, 0, 0).unwrap()));
-                    }
+    for CompiledMapping { mapping, transform } in &mapper.mappings {
+        mapped_keys.extend(mapping.from.iter());
+
+        let source = mapping
+            .from
+            .iter()
+            .find_map(|key| map.get(key).filter(|v| !v.is_null()));
+        let source = match (transform, &item) {
+            (Some(ast), Some(item)) => mapper
+                .transform(ast, source, item)
+                .with_context(|| format!("Transform for '{}' failed", mapping.to))?
+                .map(Cow::Owned),
+            _ => source.map(Cow::Borrowed),
+        };
+
+        let (value, from_default) = match source {
+            Some(value) => (value, false),
+            None => match &mapping.default {
+                Some(default) => (Cow::Borrowed(default), true),
+                None => continue,
+            },
+        };
+
+        let text = || match mapping.kind {
+            DataKind::HtmlText => value.as_str().map(|s| html_to_text(s, false)),
+            DataKind::HtmlParagraphs => value.as_str().map(|s| html_to_text(s, true)),
+            _ => value.as_str().map(String::from),
+        };
+
+        match mapping.to.as_str() {
+            "id" => id = text(),
+            "content" => content = text(),
+            "date" => {
+                if let DataKind::Date { format } = &mapping.kind
+                    && let Some(s) = value.as_str()
+                {
+                    date = Some(if from_default && s == "now" {
+                        Utc::now()
+                    } else {
+                        parse_date(s, format)?
+                    });
                 }
-                _ => {
-                    if let Some(s) = value.as_str() {
-                        metadata.insert(mapping.to.clone(), s.to_string());
-                    }
+            }
+            _ => {
+                if let Some(s) = text() {
+                    metadata.insert(mapping.to.clone(), s);
                 }
             }
         }
     }
 
-    // Collect unmapped fields into metadata
+    if let Some(template) = &mapper.content_template {
+        mapped_keys.extend(mapper.template_keys.iter());
+        let rendered = render_content(template, data)?;
+        if !rendered.is_empty() {
+            content = Some(rendered);
+        }
+    }
+
+    // Collect unmapped fields into metadata, as allowed by the passthrough policy
+    let passthrough = mapper.passthrough;
     for (key, value) in map {
         if !mapped_keys.contains(key)
+            && passes_through(&passthrough.mode, key)
             && let Some(s) = value.as_str()
         {
-            metadata.insert(key.clone(), s.to_string());
+            let s = match passthrough.max_length {
+                Some(max_length) => s.chars().take(max_length).collect(),
+                None => s.to_string(),
+            };
+            metadata.insert(key.clone(), s);
         }
     }
 
@@ -77,3 +465,81 @@ fn extract_single_export_item(
         metadata,
     })
 }
+
+/// Renders the content template for a raw item, trimming surrounding whitespace.
+fn render_content(template: &Template, data: &Value) -> anyhow::Result<String> {
+    let rendered = template.render(|placeholder| {
+        let (path, default) = split_default(placeholder);
+        let text = match json_path::get(data, path) {
+            Some(Value::String(s)) => s.clone(),
+            Some(Value::Array(values)) => values
+                .iter()
+                .map(|v| match v {
+                    Value::String(s) => s.clone(),
+                    v => v.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(" "),
+            Some(v @ (Value::Number(_) | Value::Bool(_))) => v.to_string(),
+            Some(Value::Null | Value::Object(_)) | None => String::new(),
+        };
+        Ok(if text.is_empty() {
+            default.unwrap_or_default().to_string()
+        } else {
+            text
+        })
+    })?;
+    Ok(rendered.trim().to_string())
+}
+
+/// Splits a `path|default` placeholder.
+fn split_default(placeholder: &str) -> (&str, Option<&str>) {
+    match placeholder.split_once('|') {
+        Some((path, default)) => (path.trim(), Some(default)),
+        None => (placeholder.trim(), None),
+    }
+}
+
+/// Whether an unmapped field should be copied into metadata.
+fn passes_through(mode: &PassthroughMode, key: &str) -> bool {
+    match mode {
+        PassthroughMode::All => true,
+        PassthroughMode::None => false,
+        PassthroughMode::Allow(patterns) => patterns.iter().any(|p| glob_match(p, key)),
+        PassthroughMode::Deny(patterns) => !patterns.iter().any(|p| glob_match(p, key)),
+    }
+}
+
+/// Matches `text` against a glob pattern where `*` matches any sequence
+/// and `?` any single character.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` in the pattern and the text index it was tried at
+    let mut backtrack = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            t = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Parses a date string with the given format, at midnight UTC.
+fn parse_date(s: &str, format: &str) -> anyhow::Result<DateTime<Utc>> {
+    let parsed_date = NaiveDate::parse_from_str(s, format)
+        .with_context(|| format!("Failed to parse date '{}' with format '{}'", s, format))?;
+    Ok(Utc.from_utc_datetime(&parsed_date.and_hms_opt(0, 0, 0).unwrap()))
+}
@@ -1,34 +1,45 @@
 use std::time::Instant;
 
 use axum::{
-    extract::Path,
+    extract::{Path, Query, State},
     http::StatusCode,
     Json,
 };
 use tracing::{error, info, instrument};
 
 use crate::{
-    actors::{get_actor_metadata, list_available_actors, ActorMetadata},
-    dto::{ArbitraryActorJob, JobCreation, Response},
-    job::{run_arbitrary_actor, run_job},
+    actors::{get_actor_metadata, list_available_actors, remote, ActorMetadata},
+    dto::{
+        ActorValidateRequest, ActorValidateResponse, ArbitraryActorJob, JobCreation,
+        PreviewRequest, PutStateRequest, Response, TokenQuery, ValidateRequest, ValidateResponse,
+        VersionQuery,
+    },
+    job::{
+        check_actor_job, load_preview_items, preview, run_arbitrary_actor, run_job,
+        validate_settings,
+    },
     metrics::{record_http_duration, record_http_request},
+    scripting::{FunctionDoc, FUNCTIONS},
+    state_store::{SharedStateStore, StateStore, StoredState},
     web_utils::AppError,
 };
 
 /// POST /:actor_type - Execute an Apify actor job
-#[instrument(skip(job), fields(actor_type = %actor_type))]
+#[instrument(skip(store, job), fields(actor_type = %actor_type))]
 pub async fn handle_job(
+    State(store): State<SharedStateStore>,
     Path(actor_type): Path<String>,
     Json(job): Json<JobCreation>,
 ) -> Result<(StatusCode, Json<Response>), AppError> {
     let start = Instant::now();
     info!("Received job request");
 
-    let response = run_job(&actor_type, &job).await.map_err(|e| {
+    let response = run_job(&actor_type, &job, store.as_deref()).await.map_err(|e| {
         error!(error = %e, "Job execution failed");
-        record_http_request("POST", &format!("/{}", actor_type), 502);
+        let err = AppError::from_job_error(e, AppError::bad_gateway);
+        record_http_request("POST", &format!("/{}", actor_type), err.status().as_u16());
         record_http_duration("POST", &format!("/{}", actor_type), start.elapsed().as_secs_f64());
-        AppError::bad_gateway(e.to_string())
+        err
     })?;
 
     record_http_request("POST", &format!("/{}", actor_type), 200);
@@ -69,20 +80,94 @@ pub async fn get_actor_schema(
     Ok(Json(metadata))
 }
 
+/// POST /actors/:actor_type/validate - Check a typed actor job without running it
+#[instrument(skip(request))]
+pub async fn handle_actor_validate(
+    Path(actor_type): Path<String>,
+    Json(request): Json<ActorValidateRequest>,
+) -> Result<(StatusCode, Json<ActorValidateResponse>), AppError> {
+    let start = Instant::now();
+    let path = format!("/actors/{}/validate", actor_type);
+    let record = |status: StatusCode| {
+        record_http_request("POST", &path, status.as_u16());
+        record_http_duration("POST", &path, start.elapsed().as_secs_f64());
+    };
+
+    let response = check_actor_job(&actor_type, &request).ok_or_else(|| {
+        record(StatusCode::NOT_FOUND);
+        AppError::not_found(format!("Unknown actor type: {}", actor_type))
+    })?;
+
+    let status = if response.valid {
+        StatusCode::OK
+    } else {
+        StatusCode::UNPROCESSABLE_ENTITY
+    };
+    record(status);
+    info!(valid = response.valid, "Checked actor job");
+    Ok((status, Json(response)))
+}
+
+/// GET /actors/remote/*actor_id - Get the input schema of an Apify store actor
+#[instrument(skip(query))]
+pub async fn get_remote_actor_schema(
+    Path(actor_id): Path<String>,
+    Query(query): Query<TokenQuery>,
+) -> Result<Json<ActorMetadata>, AppError> {
+    let start = Instant::now();
+    let record = |status: u16| {
+        record_http_request("GET", "/actors/remote/{actor_id}", status);
+        record_http_duration("GET", "/actors/remote/{actor_id}", start.elapsed().as_secs_f64());
+    };
+
+    let metadata = remote::get(&actor_id, query.token.as_deref())
+        .await
+        .and_then(|actor| actor.metadata())
+        .map_err(|e| {
+            error!(error = %e, "Failed to fetch remote actor");
+            let not_found = e
+                .downcast_ref::<reqwest::Error>()
+                .and_then(reqwest::Error::status)
+                .is_some_and(|status| status == StatusCode::NOT_FOUND);
+            let err = if not_found {
+                AppError::not_found(format!("Unknown Apify actor: {}", actor_id))
+            } else {
+                AppError::bad_gateway(format!("{:#}", e))
+            };
+            record(err.status().as_u16());
+            err
+        })?;
+
+    record(200);
+    info!("Retrieved remote actor schema");
+    Ok(Json(metadata))
+}
+
+/// GET /functions - List the helper functions available in Rhai expressions
+#[instrument]
+pub async fn list_functions() -> Json<&'static [FunctionDoc]> {
+    let start = Instant::now();
+    record_http_request("GET", "/functions", 200);
+    record_http_duration("GET", "/functions", start.elapsed().as_secs_f64());
+    Json(FUNCTIONS)
+}
+
 /// POST /run - Execute an arbitrary Apify actor job
-#[instrument(skip(job), fields(actor_id = %job.settings.actor_id))]
+#[instrument(skip(store, job), fields(actor_id = %job.settings.actor_id))]
 pub async fn handle_arbitrary_actor(
+    State(store): State<SharedStateStore>,
     Json(job): Json<ArbitraryActorJob>,
 ) -> Result<(StatusCode, Json<Response>), AppError> {
     let start = Instant::now();
     let actor_id = job.settings.actor_id.clone();
     info!("Received arbitrary actor job request");
 
-    let response = run_arbitrary_actor(&job).await.map_err(|e| {
+    let response = run_arbitrary_actor(&job, store.as_deref()).await.map_err(|e| {
         error!(error = %e, "Arbitrary actor job execution failed");
-        record_http_request("POST", "/run", 502);
+        let err = AppError::from_job_error(e, AppError::bad_gateway);
+        record_http_request("POST", "/run", err.status().as_u16());
         record_http_duration("POST", "/run", start.elapsed().as_secs_f64());
-        AppError::bad_gateway(e.to_string())
+        err
     })?;
 
     record_http_request("POST", "/run", 200);
@@ -91,3 +176,154 @@ pub async fn handle_arbitrary_actor(
 
     Ok((StatusCode::OK, Json(response)))
 }
+
+/// POST /preview - Apply mappings to sample items without running an actor
+#[instrument(skip(request))]
+pub async fn handle_preview(
+    Json(request): Json<PreviewRequest>,
+) -> Result<(StatusCode, Json<Response>), AppError> {
+    let start = Instant::now();
+    info!("Received preview request");
+
+    let record = |status: u16| {
+        record_http_request("POST", "/preview", status);
+        record_http_duration("POST", "/preview", start.elapsed().as_secs_f64());
+    };
+
+    if request.items.is_none() && (request.dataset_id.is_none() || request.token.is_none()) {
+        record(400);
+        return Err(AppError::bad_request(
+            "Either items, or dataset_id with a token, is required",
+        ));
+    }
+
+    let items = load_preview_items(&request).await.map_err(|e| {
+        error!(error = %e, "Failed to load preview items");
+        record(502);
+        AppError::bad_gateway(e.to_string())
+    })?;
+
+    let response = preview(items, &request).map_err(|e| {
+        error!(error = %e, "Preview failed");
+        record(400);
+        AppError::from_job_error(e, AppError::bad_request)
+    })?;
+
+    record(200);
+    info!(result_count = response.result.len(), "Preview completed");
+
+    Ok((StatusCode::OK, Json(response)))
+}
+
+/// POST /validate - Check mappings and settings without running anything
+#[instrument(skip(request))]
+pub async fn handle_validate(
+    Json(request): Json<ValidateRequest>,
+) -> Result<Json<ValidateResponse>, AppError> {
+    let start = Instant::now();
+
+    validate_settings(
+        &request.key_mapping,
+        request.state_mapping.as_ref(),
+        &request.extraction,
+        &request.state,
+    )
+    .map_err(|e| {
+        info!(error = %e, "Mappings are invalid");
+        record_http_request("POST", "/validate", 400);
+        record_http_duration("POST", "/validate", start.elapsed().as_secs_f64());
+        AppError::from_job_error(e, AppError::bad_request)
+    })?;
+
+    record_http_request("POST", "/validate", 200);
+    record_http_duration("POST", "/validate", start.elapsed().as_secs_f64());
+    info!("Mappings are valid");
+    Ok(Json(ValidateResponse { valid: true }))
+}
+
+fn enabled_store(store: &SharedStateStore) -> Result<&StateStore, AppError> {
+    store
+        .as_deref()
+        .ok_or_else(|| AppError::not_found("The state store is disabled, set DATA_DIR to enable it"))
+}
+
+/// GET /state/:job_name - Get the stored state of a job
+#[instrument(skip(store))]
+pub async fn get_state(
+    State(store): State<SharedStateStore>,
+    Path(job_name): Path<String>,
+) -> Result<Json<StoredState>, AppError> {
+    let start = Instant::now();
+    let record = |status: u16| {
+        record_http_request("GET", "/state/{job_name}", status);
+        record_http_duration("GET", "/state/{job_name}", start.elapsed().as_secs_f64());
+    };
+
+    let stored = async {
+        enabled_store(&store)?
+            .get_async(&job_name)
+            .await?
+            .ok_or_else(|| AppError::not_found(format!("No state stored for job '{}'", job_name)))
+    }
+    .await
+    .inspect_err(|e| record(e.status().as_u16()))?;
+
+    record(200);
+    Ok(Json(stored))
+}
+
+/// PUT /state/:job_name - Overwrite the stored state of a job
+#[instrument(skip(store, request))]
+pub async fn put_state(
+    State(store): State<SharedStateStore>,
+    Path(job_name): Path<String>,
+    Json(request): Json<PutStateRequest>,
+) -> Result<Json<StoredState>, AppError> {
+    let start = Instant::now();
+    let record = |status: u16| {
+        record_http_request("PUT", "/state/{job_name}", status);
+        record_http_duration("PUT", "/state/{job_name}", start.elapsed().as_secs_f64());
+    };
+
+    let stored = async {
+        Ok::<_, AppError>(
+            enabled_store(&store)?
+                .put_async(&job_name, request.state, request.version)
+                .await?,
+        )
+    }
+    .await
+    .inspect_err(|e| record(e.status().as_u16()))?;
+
+    record(200);
+    info!(version = stored.version, "Stored job state");
+    Ok(Json(stored))
+}
+
+/// DELETE /state/:job_name - Delete the stored state of a job
+#[instrument(skip(store))]
+pub async fn delete_state(
+    State(store): State<SharedStateStore>,
+    Path(job_name): Path<String>,
+    Query(query): Query<VersionQuery>,
+) -> Result<StatusCode, AppError> {
+    let start = Instant::now();
+    let record = |status: u16| {
+        record_http_request("DELETE", "/state/{job_name}", status);
+        record_http_duration("DELETE", "/state/{job_name}", start.elapsed().as_secs_f64());
+    };
+
+    async {
+        enabled_store(&store)?
+            .delete_async(&job_name, query.version)
+            .await?
+            .then_some(())
+            .ok_or_else(|| AppError::not_found(format!("No state stored for job '{}'", job_name)))
+    }
+    .await
+    .inspect_err(|e| record(e.status().as_u16()))?;
+
+    record(204);
+    info!("Deleted job state");
+    Ok(StatusCode::NO_CONTENT)
+}
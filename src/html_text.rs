@@ -0,0 +1,207 @@
+/// Marks the boundary of a block-level element in the intermediate text
+const PARAGRAPH: char = '\u{1}';
+/// Marks a `<br>` in the intermediate text
+const LINE_BREAK: char = '\u{2}';
+
+/// Elements whose boundaries separate paragraphs
+const BLOCK_TAGS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "dd",
+    "div",
+    "dl",
+    "dt",
+    "figcaption",
+    "figure",
+    "footer",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "li",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "tr",
+    "ul",
+];
+
+/// Elements whose content is never text
+const SKIPPED_TAGS: &[&str] = &["script", "style", "noscript", "template"];
+
+/// Converts an HTML fragment to plain text.
+///
+/// Tags, comments and script/style contents are removed, entities are decoded and
+/// whitespace is collapsed. With `keep_paragraphs`, block-level elements are separated
+/// by a blank line and `<br>` by a newline; otherwise everything ends up on one line.
+pub fn html_to_text(html: &str, keep_paragraphs: bool) -> String {
+    let stripped = strip_tags(html);
+
+    let paragraphs = stripped
+        .split(PARAGRAPH)
+        .map(|paragraph| {
+            paragraph
+                .split(LINE_BREAK)
+                .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+                .filter(|line| !line.is_empty())
+                .collect::<Vec<_>>()
+                .join(if keep_paragraphs { "\n" } else { " " })
+        })
+        .filter(|paragraph| !paragraph.is_empty())
+        .collect::<Vec<_>>();
+
+    paragraphs.join(if keep_paragraphs { "\n\n" } else { " " })
+}
+
+/// Removes markup, replacing block boundaries and line breaks with markers.
+fn strip_tags(html: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut rest = html;
+
+    while let Some(start) = rest.find('<') {
+        push_text(&mut out, &rest[..start]);
+        rest = &rest[start..];
+
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+
+        let starts_tag = rest[1..]
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '/' || c == '!');
+        let Some(end) = rest.find('>').filter(|_| starts_tag) else {
+            // Like browsers, a '<' that does not open a tag is just text
+            out.push('<');
+            rest = &rest[1..];
+            continue;
+        };
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+
+        let closing = tag.starts_with('/');
+        let name: String = tag
+            .trim_start_matches('/')
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_ascii_lowercase();
+
+        if !closing && SKIPPED_TAGS.contains(&name.as_str()) {
+            let close = format!("</{}", name);
+            rest = find_ascii_case_insensitive(rest, &close)
+                .and_then(|pos| rest[pos..].find('>').map(|end| &rest[pos + end + 1..]))
+                .unwrap_or("");
+        } else if name == "br" {
+            out.push(LINE_BREAK);
+        } else if BLOCK_TAGS.contains(&name.as_str()) {
+            out.push(PARAGRAPH);
+        }
+    }
+    push_text(&mut out, rest);
+    out
+}
+
+/// Appends decoded text, dropping any characters that collide with the markers.
+fn push_text(out: &mut String, text: &str) {
+    let decoded = decode_entities(text);
+    out.extend(
+        decoded
+            .chars()
+            .filter(|&c| c != PARAGRAPH && c != LINE_BREAK),
+    );
+}
+
+fn find_ascii_case_insensitive(haystack: &str, needle: &str) -> Option<usize> {
+    haystack
+        .as_bytes()
+        .windows(needle.len())
+        .position(|window| window.eq_ignore_ascii_case(needle.as_bytes()))
+}
+
+/// Decodes named, decimal and hexadecimal character references.
+/// Unknown references are kept verbatim.
+fn decode_entities(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let decoded = rest[1..]
+            .find(';')
+            .filter(|&end| end > 0 && end <= 10)
+            .and_then(|end| decode_entity(&rest[1..=end]).map(|c| (c, end + 2)));
+        match decoded {
+            Some((c, len)) => {
+                out.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn decode_entity(entity: &str) -> Option<char> {
+    if let Some(num) = entity.strip_prefix('#') {
+        let code = match num.strip_prefix(['x', 'X']) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => num.parse().ok()?,
+        };
+        return char::from_u32(code);
+    }
+    Some(match entity {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => ' ',
+        "copy" => '©',
+        "reg" => '®',
+        "trade" => '™',
+        "hellip" => '…',
+        "mdash" => '—',
+        "ndash" => '–',
+        "lsquo" => '‘',
+        "rsquo" => '’',
+        "ldquo" => '“',
+        "rdquo" => '”',
+        "laquo" => '«',
+        "raquo" => '»',
+        "bull" => '•',
+        "middot" => '·',
+        "deg" => '°',
+        "euro" => '€',
+        "eacute" => 'é',
+        "egrave" => 'è',
+        "ecirc" => 'ê',
+        "agrave" => 'à',
+        "acirc" => 'â',
+        "ccedil" => 'ç',
+        "ocirc" => 'ô',
+        "ugrave" => 'ù',
+        "uuml" => 'ü',
+        "ouml" => 'ö',
+        "auml" => 'ä',
+        "szlig" => 'ß',
+        _ => return None,
+    })
+}
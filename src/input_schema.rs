@@ -0,0 +1,139 @@
+use jsonschema::Validator;
+use serde::Serialize;
+use serde_json::Value;
+use thiserror::Error;
+
+/// A value of an actor input that breaks a rule of its JSON Schema
+#[derive(Debug, Clone, Serialize)]
+pub struct SchemaViolation {
+    /// JSON pointer to the value, empty for the input itself
+    pub pointer: String,
+    pub message: String,
+}
+
+/// Every violation found in an actor input
+#[derive(Debug, Error)]
+#[error("Invalid {target}: {}", describe(.violations))]
+pub struct SchemaErrors {
+    /// What was checked, e.g. `web_scraper config` or `actor_input`
+    pub target: String,
+    pub violations: Vec<SchemaViolation>,
+}
+
+impl SchemaErrors {
+    /// A single violation of the whole input
+    pub fn input(target: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            target: target.into(),
+            violations: vec![SchemaViolation {
+                pointer: String::new(),
+                message: message.into(),
+            }],
+        }
+    }
+}
+
+fn describe(violations: &[SchemaViolation]) -> String {
+    violations
+        .iter()
+        .map(|v| match v.pointer.as_str() {
+            "" => v.message.clone(),
+            pointer => format!("{}: {}", pointer, v.message),
+        })
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// A compiled JSON Schema of an actor input
+#[derive(Debug)]
+pub struct InputSchema {
+    validator: Validator,
+}
+
+impl InputSchema {
+    pub fn compile(schema: &Value) -> anyhow::Result<Self> {
+        let validator = jsonschema::validator_for(schema)
+            .map_err(|e| anyhow::anyhow!("Invalid JSON Schema: {}", e))?;
+        Ok(Self { validator })
+    }
+
+    /// Compiles the input schema of an Apify actor. Apify fills in the `default` of missing
+    /// fields and accepts `null` for `nullable` ones, which plain JSON Schema does not.
+    pub fn compile_apify(schema: &Value) -> anyhow::Result<Self> {
+        let mut schema = schema.clone();
+        normalize_apify(&mut schema);
+        Self::compile(&schema)
+    }
+
+    /// Checks an input, reporting every violation
+    pub fn check(&self, target: &str, input: &Value) -> Result<(), SchemaErrors> {
+        let violations: Vec<SchemaViolation> = self
+            .validator
+            .iter_errors(input)
+            .map(|e| SchemaViolation {
+                pointer: e.instance_path.to_string(),
+                message: e.to_string(),
+            })
+            .collect();
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(SchemaErrors {
+                target: target.to_string(),
+                violations,
+            })
+        }
+    }
+}
+
+/// Rewrites Apify schema extensions into JSON Schema: `nullable` fields also accept
+/// `null`, and fields with a `default` are no longer required
+fn normalize_apify(schema: &mut Value) {
+    let Value::Object(schema) = schema else {
+        return;
+    };
+
+    if schema.get("nullable") == Some(&Value::Bool(true)) {
+        match schema.get_mut("type") {
+            Some(Value::String(kind)) => {
+                let kind = Value::String(kind.clone());
+                schema.insert("type".to_string(), Value::from(vec![kind, "null".into()]));
+            }
+            Some(Value::Array(kinds)) if !kinds.contains(&"null".into()) => {
+                kinds.push("null".into())
+            }
+            _ => {}
+        }
+        if let Some(Value::Array(values)) = schema.get_mut("enum")
+            && !values.contains(&Value::Null)
+        {
+            values.push(Value::Null);
+        }
+    }
+
+    if let Some(Value::Object(properties)) = schema.get("properties") {
+        let defaulted: Vec<String> = properties
+            .iter()
+            .filter(|(_, property)| property.get("default").is_some())
+            .map(|(name, _)| name.clone())
+            .collect();
+        if let Some(Value::Array(required)) = schema.get_mut("required") {
+            required.retain(|name| {
+                !name
+                    .as_str()
+                    .is_some_and(|name| defaulted.iter().any(|d| d == name))
+            });
+        }
+    }
+
+    for key in ["properties", "patternProperties"] {
+        if let Some(Value::Object(properties)) = schema.get_mut(key) {
+            properties.values_mut().for_each(normalize_apify);
+        }
+    }
+    for key in ["items", "additionalProperties"] {
+        if let Some(nested) = schema.get_mut(key) {
+            normalize_apify(nested);
+        }
+    }
+}
@@ -4,84 +4,104 @@ use serde_json::Value;
 use tracing::{debug, error, info, instrument, warn};
 
 use crate::{
-    actors::ActorConfig,
-    client::{ApiFyClient, State},
-    dto::{ArbitraryActorJob, Data, ExportItem, JobCreation, KeyMapping, Response, StateMapping},
-    extraction::extract_export_items,
-    mapping_utils::{self, update_state, update_state_core},
+    actors::{find_actor, remote, ActorConfig},
+    client::{ApiFyClient, DatasetId, State},
+    dto::{
+        ActorValidateRequest, ActorValidateResponse, ArbitraryActorJob, Data, ExtractionSettings,
+        JobCreation, JobState, KeyMapping, PreviewRequest, Response, StateMapping,
+    },
+    extraction::{extract_export_items, Extraction, ExtractionContext},
+    input_schema::{InputSchema, SchemaErrors},
+    mapping_utils::{self, apply_state_mapping, update_state_core},
     metrics::{record_job_started, Timer},
+    state_store::StateStore,
+    validation::{validate_mappings, FieldError, ValidationErrors},
 };
 
 /// Maximum number of poll attempts before timing out (5 minutes at 1 second intervals)
 const MAX_POLL_ATTEMPTS: u32 = 300;
 
-/// Merges state mappings into a request body
-fn apply_state_mapping(
-    body: &mut HashMap<String, Value>,
-    state_str: &str,
-    state_mapping: Option<&Vec<StateMapping>>,
-) -> anyhow::Result<()> {
-    let state: HashMap<String, Value> = serde_json::from_str(state_str)?;
-    if let Some(mapping) = state_mapping {
-        for m in mapping {
-            if let Some(v) = state.get(&m.from) {
-                body.insert(m.to.clone(), v.clone());
-            }
-        }
+/// Builds the extraction context from the job state.
+fn extraction_context(state: &JobState) -> ExtractionContext {
+    ExtractionContext {
+        state: state.clone(),
+        run_id: None,
     }
-    Ok(())
-}
-
-/// Prepares the request body by merging actor config with state mappings.
-#[instrument(skip(actor_config, job), fields(actor_type = %actor_config.actor_type()))]
-fn prepare_body(
-    actor_config: &ActorConfig,
-    job: &JobCreation,
-) -> anyhow::Result<HashMap<String, Value>> {
-    let mut body = actor_config
-        .to_body()
-        .map_err(|e| anyhow::anyhow!("Failed to serialize actor config: {}", e))?;
-
-    apply_state_mapping(&mut body, &job.state, job.settings.state_mapping.as_ref())?;
-    Ok(body)
 }
 
 /// Prepares the request body for an arbitrary actor by merging input with state mappings.
-#[instrument(skip(job), fields(actor_id = %job.settings.actor_id))]
-fn prepare_arbitrary_body(job: &ArbitraryActorJob) -> anyhow::Result<HashMap<String, Value>> {
+#[instrument(skip(job, state), fields(actor_id = %job.settings.actor_id))]
+fn prepare_arbitrary_body(
+    job: &ArbitraryActorJob,
+    state: &JobState,
+) -> anyhow::Result<HashMap<String, Value>> {
     let mut body: HashMap<String, Value> = match &job.settings.actor_input {
         Value::Object(map) => map.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
         _ => HashMap::new(),
     };
 
-    apply_state_mapping(&mut body, &job.state, job.settings.state_mapping.as_ref())?;
+    apply_state_mapping(&mut body, state, job.settings.state_mapping.as_ref())?;
     Ok(body)
 }
 
+/// Checks the input of an arbitrary actor against the input schema of its latest build.
+/// Actors whose schema cannot be fetched or compiled, or that have none, are not checked.
+#[instrument(skip(token, body))]
+pub async fn check_actor_input(
+    actor_id: &str,
+    token: &str,
+    body: &HashMap<String, Value>,
+) -> anyhow::Result<()> {
+    let actor = match remote::get(actor_id, Some(token)).await {
+        Ok(actor) => actor,
+        Err(e) => {
+            warn!(error = %e, "Cannot fetch the input schema, skipping the check of the actor input");
+            return Ok(());
+        }
+    };
+    let Some(schema) = &actor.input_schema else {
+        debug!("Actor has no input schema");
+        return Ok(());
+    };
+    let schema = match InputSchema::compile_apify(schema) {
+        Ok(schema) => schema,
+        Err(e) => {
+            warn!(error = %e, "Skipping the check of the actor input");
+            return Ok(());
+        }
+    };
+    let input = Value::Object(body.clone().into_iter().collect());
+    schema.check("actor_input", &input)?;
+    Ok(())
+}
+
 /// Starts an Apify actor job.
-#[instrument(skip(client, actor_config, job), fields(actor_type = %actor_config.actor_type()))]
+#[instrument(skip(client, actor_config, body), fields(actor_type = %actor_config.actor_type()))]
 async fn start_job(
     client: &ApiFyClient,
     actor_config: &ActorConfig,
-    job: &JobCreation,
+    body: &HashMap<String, Value>,
 ) -> anyhow::Result<Data> {
     debug!("Validating actor configuration");
     actor_config
         .validate()
         .map_err(|e| anyhow::anyhow!("Invalid actor configuration: {}", e))?;
 
-    let body = prepare_body(actor_config, job)?;
     info!("Starting Apify actor job");
-    client.start_job(actor_config.actor_name(), &body).await
+    client.start_job(actor_config.actor_name(), body).await
 }
 
 /// Polls for job completion and downloads results.
-#[instrument(skip(client, key_mapping, data), fields(run_id = %data.id.0, dataset_id = %data.default_dataset_id.0))]
+#[instrument(skip(client, actor_config, key_mapping, settings, ctx, data), fields(run_id = %data.id.0, dataset_id = %data.default_dataset_id.0))]
 async fn poll_and_fetch_results(
     client: &ApiFyClient,
+    actor_config: Option<&ActorConfig>,
     key_mapping: &[KeyMapping],
+    settings: &ExtractionSettings,
+    mut ctx: ExtractionContext,
     data: Data,
-) -> anyhow::Result<Vec<ExportItem>> {
+) -> anyhow::Result<Extraction> {
+    ctx.run_id = Some(data.id.0.clone());
     let mut poll_count = 0u32;
 
     loop {
@@ -102,10 +122,28 @@ async fn poll_and_fetch_results(
                 }
                 State::Succeeded => {
                     info!(poll_count, "Job succeeded, downloading results");
-                    let raw_data = client.download_results(&data.default_dataset_id).await?;
-                    let items = extract_export_items(raw_data, key_mapping)?;
-                    info!(item_count = items.len(), "Extracted export items");
-                    return Ok(items);
+                    let mut raw_data = client.download_results(&data.default_dataset_id).await?;
+                    if let Some(actor_config) = actor_config {
+                        raw_data = raw_data
+                            .into_iter()
+                            .map(|item| actor_config.normalize_output(item))
+                            .collect();
+                    }
+                    let extraction = extract_export_items(raw_data, key_mapping, settings, &ctx)?;
+                    let report = &extraction.report;
+                    if report.dropped > 0 {
+                        warn!(
+                            seen = report.seen,
+                            dropped = report.dropped,
+                            "Some items were dropped during extraction"
+                        );
+                    }
+                    info!(
+                        item_count = report.kept,
+                        filtered = report.filtered,
+                        "Extracted export items"
+                    );
+                    return Ok(extraction);
                 }
                 State::Failed => {
                     error!("Actor job failed");
@@ -121,29 +159,168 @@ async fn poll_and_fetch_results(
     }
 }
 
-/// Validates that state mapping expressions are valid.
-#[instrument(skip(job))]
-pub fn validate_state_mapping(job: &JobCreation) -> anyhow::Result<()> {
-    debug!("Validating state mapping expressions");
-    update_state(&vec![], job, mapping_utils::Context::new())?;
+/// Validates mappings and settings up front, then dry-runs the state mapping
+/// expressions against the current state.
+#[instrument(skip_all)]
+pub fn validate_settings(
+    key_mapping: &[KeyMapping],
+    state_mapping: Option<&Vec<StateMapping>>,
+    extraction: &ExtractionSettings,
+    state: &JobState,
+) -> anyhow::Result<()> {
+    debug!("Validating mappings");
+    validate_mappings(key_mapping, state_mapping, extraction)?;
+    update_state_core(&[], state, state_mapping, mapping_utils::Context::new())?;
     Ok(())
 }
 
+/// Checks a typed actor job without running it: the config against the actor's schema
+/// and its own checks, the mappings, and the config once state is placed. Every problem
+/// is collected. Returns `None` for an unknown actor type.
+#[instrument(skip(request))]
+pub fn check_actor_job(
+    actor_type: &str,
+    request: &ActorValidateRequest,
+) -> Option<ActorValidateResponse> {
+    let actor = find_actor(actor_type)?;
+    let key_mapping = actor.key_mapping(&request.key_mapping);
+    let mut errors = Vec::new();
+    let mut violations = Vec::new();
+    let config_error = |message: String| FieldError {
+        field: "actor_config".to_string(),
+        message,
+    };
+
+    let config = match ActorConfig::from_type_and_config(actor_type, request.actor_config.clone())
+    {
+        Ok(config) => match config.validate() {
+            Ok(()) => Some(config),
+            Err(message) => {
+                errors.push(config_error(message));
+                None
+            }
+        },
+        Err(e) => {
+            match e.downcast::<SchemaErrors>() {
+                Ok(schema_errors) => violations = schema_errors.violations,
+                Err(e) => errors.push(config_error(format!("{:#}", e))),
+            }
+            None
+        }
+    };
+
+    if let Err(e) = validate_settings(
+        &key_mapping,
+        request.state_mapping.as_ref(),
+        &request.extraction,
+        &request.state,
+    ) {
+        match e.downcast::<ValidationErrors>() {
+            Ok(ValidationErrors(mapping_errors)) => errors.extend(mapping_errors),
+            Err(e) => errors.push(FieldError {
+                field: "state_mapping".to_string(),
+                message: format!("{:#}", e),
+            }),
+        }
+    }
+
+    // State is only placed once the config and the state mapping are valid on their own
+    let state_mapping_valid = !errors.iter().any(|e| e.field.starts_with("state_mapping"));
+    let body = match config.filter(|_| state_mapping_valid) {
+        Some(config) => {
+            match config.body_with_state(&request.state, request.state_mapping.as_ref()) {
+                Ok(body) => Some(body),
+                Err(ValidationErrors(state_errors)) => {
+                    errors.extend(state_errors);
+                    None
+                }
+            }
+        }
+        None => None,
+    };
+
+    Some(ActorValidateResponse {
+        valid: errors.is_empty() && violations.is_empty(),
+        body,
+        key_mapping,
+        errors,
+        violations,
+    })
+}
+
+/// Resolves the state a job starts from, with the version to expect when saving it.
+///
+/// Named jobs read their state from the store, starting from the request state until
+/// one is saved. Unnamed jobs use the request state and are never saved.
+async fn load_state(
+    store: Option<&StateStore>,
+    job_name: Option<&str>,
+    state: &JobState,
+) -> anyhow::Result<(JobState, Option<u64>)> {
+    let Some(job_name) = job_name else {
+        return Ok((state.clone(), None));
+    };
+    let Some(store) = store else {
+        return Err(ValidationErrors::field(
+            "job_name",
+            "The state store is disabled, set DATA_DIR to enable it",
+        )
+        .into());
+    };
+    Ok(match store.get_async(job_name).await? {
+        Some(stored) => {
+            debug!(job_name, version = stored.version, "Loaded stored state");
+            (stored.state, Some(stored.version))
+        }
+        None => (state.clone(), Some(0)),
+    })
+}
+
+/// Saves the state of a named job, failing if it changed since it was loaded.
+async fn save_state(
+    store: Option<&StateStore>,
+    job_name: Option<&str>,
+    state: &JobState,
+    version: Option<u64>,
+) -> anyhow::Result<Option<u64>> {
+    match (store, job_name, version) {
+        (Some(store), Some(job_name), Some(expected)) => {
+            let stored = store
+                .put_async(job_name, state.clone(), Some(expected))
+                .await?;
+            Ok(Some(stored.version))
+        }
+        _ => Ok(None),
+    }
+}
+
 /// Runs a complete job: start, poll, fetch results, update state.
-#[instrument(skip(job), fields(actor_type = %actor_type))]
-pub async fn run_job(actor_type: &str, job: &JobCreation) -> anyhow::Result<Response> {
-    let actor_config = ActorConfig::from_type_and_config(actor_type, job.settings.actor_config.clone())
-        .map_err(|e| anyhow::anyhow!("{}", e))?;
+#[instrument(skip(job, store), fields(actor_type = %actor_type))]
+pub async fn run_job(
+    actor_type: &str,
+    job: &JobCreation,
+    store: Option<&StateStore>,
+) -> anyhow::Result<Response> {
+    let actor_config = ActorConfig::from_type_and_config(actor_type, job.settings.actor_config.clone())?;
+
+    let key_mapping = actor_config.key_mapping(&job.settings.key_mapping);
+
+    let (state, version) = load_state(store, job.job_name.as_deref(), &job.state).await?;
+    validate_settings(
+        &key_mapping,
+        job.settings.state_mapping.as_ref(),
+        &job.settings.extraction,
+        &state,
+    )?;
+    let body = actor_config.body_with_state(&state, job.settings.state_mapping.as_ref())?;
 
     record_job_started(actor_type);
     let timer = Timer::new(actor_type);
 
     info!("Starting job execution");
 
-    validate_state_mapping(job)?;
-
     let client = ApiFyClient::new(&job.settings.token);
-    let data = match start_job(&client, &actor_config, job).await {
+    let data = match start_job(&client, &actor_config, &body).await {
         Ok(data) => {
             info!(run_id = %data.id.0, "Job started successfully");
             data
@@ -155,8 +332,17 @@ pub async fn run_job(actor_type: &str, job: &JobCreation) -> anyhow::Result<Resp
         }
     };
 
-    let result = match poll_and_fetch_results(&client, &job.settings.key_mapping, data).await {
-        Ok(result) => result,
+    let extraction = match poll_and_fetch_results(
+        &client,
+        Some(&actor_config),
+        &key_mapping,
+        &job.settings.extraction,
+        extraction_context(&state),
+        data,
+    )
+    .await
+    {
+        Ok(extraction) => extraction,
         Err(e) => {
             error!(error = %e, "Failed to fetch results");
             timer.finish_failed();
@@ -164,8 +350,13 @@ pub async fn run_job(actor_type: &str, job: &JobCreation) -> anyhow::Result<Resp
         }
     };
 
-    let ctx = mapping_utils::Context::new();
-    let state = match update_state(&result, job, ctx) {
+    let Extraction {
+        items: result,
+        report,
+        seen_digests,
+    } = extraction;
+    let ctx = mapping_utils::Context::new().with_seen_digests(seen_digests);
+    let state = match update_state_core(&result, &state, job.settings.state_mapping.as_ref(), ctx) {
         Ok(state) => state,
         Err(e) => {
             error!(error = %e, "Failed to update state");
@@ -173,34 +364,48 @@ pub async fn run_job(actor_type: &str, job: &JobCreation) -> anyhow::Result<Resp
             return Err(e);
         }
     };
+    let state_version = match save_state(store, job.job_name.as_deref(), &state, version).await {
+        Ok(state_version) => state_version,
+        Err(e) => {
+            error!(error = %e, "Failed to save state");
+            timer.finish_failed();
+            return Err(e);
+        }
+    };
 
     timer.finish_success();
     info!(result_count = result.len(), "Job completed successfully");
 
-    Ok(Response { state, result })
+    Ok(Response {
+        state_version,
+        ..Response::new(state, result, report)
+    })
 }
 
 /// Runs an arbitrary Apify actor job.
-#[instrument(skip(job), fields(actor_id = %job.settings.actor_id))]
-pub async fn run_arbitrary_actor(job: &ArbitraryActorJob) -> anyhow::Result<Response> {
+#[instrument(skip(job, store), fields(actor_id = %job.settings.actor_id))]
+pub async fn run_arbitrary_actor(
+    job: &ArbitraryActorJob,
+    store: Option<&StateStore>,
+) -> anyhow::Result<Response> {
     let actor_id = &job.settings.actor_id;
 
+    let (state, version) = load_state(store, job.job_name.as_deref(), &job.state).await?;
+    validate_settings(
+        &job.settings.key_mapping,
+        job.settings.state_mapping.as_ref(),
+        &job.settings.extraction,
+        &state,
+    )?;
+
+    let body = prepare_arbitrary_body(job, &state)?;
+    check_actor_input(actor_id, &job.settings.token, &body).await?;
+
     record_job_started(actor_id);
     let timer = Timer::new(actor_id);
 
     info!("Starting arbitrary actor job execution");
 
-    // Validate state mapping
-    debug!("Validating state mapping expressions");
-    update_state_core(
-        &vec![],
-        &job.state,
-        job.settings.state_mapping.as_ref(),
-        mapping_utils::Context::new(),
-    )?;
-
-    let body = prepare_arbitrary_body(job)?;
-
     let client = ApiFyClient::new(&job.settings.token);
     let data = match client.start_job(actor_id, &body).await {
         Ok(data) => {
@@ -214,8 +419,17 @@ pub async fn run_arbitrary_actor(job: &ArbitraryActorJob) -> anyhow::Result<Resp
         }
     };
 
-    let result = match poll_and_fetch_results(&client, &job.settings.key_mapping, data).await {
-        Ok(result) => result,
+    let extraction = match poll_and_fetch_results(
+        &client,
+        None,
+        &job.settings.key_mapping,
+        &job.settings.extraction,
+        extraction_context(&state),
+        data,
+    )
+    .await
+    {
+        Ok(extraction) => extraction,
         Err(e) => {
             error!(error = %e, "Failed to fetch results");
             timer.finish_failed();
@@ -223,10 +437,15 @@ pub async fn run_arbitrary_actor(job: &ArbitraryActorJob) -> anyhow::Result<Resp
         }
     };
 
-    let ctx = mapping_utils::Context::new();
+    let Extraction {
+        items: result,
+        report,
+        seen_digests,
+    } = extraction;
+    let ctx = mapping_utils::Context::new().with_seen_digests(seen_digests);
     let state = match update_state_core(
         &result,
-        &job.state,
+        &state,
         job.settings.state_mapping.as_ref(),
         ctx,
     ) {
@@ -237,9 +456,68 @@ pub async fn run_arbitrary_actor(job: &ArbitraryActorJob) -> anyhow::Result<Resp
             return Err(e);
         }
     };
+    let state_version = match save_state(store, job.job_name.as_deref(), &state, version).await {
+        Ok(state_version) => state_version,
+        Err(e) => {
+            error!(error = %e, "Failed to save state");
+            timer.finish_failed();
+            return Err(e);
+        }
+    };
 
     timer.finish_success();
     info!(result_count = result.len(), "Arbitrary actor job completed successfully");
 
-    Ok(Response { state, result })
+    Ok(Response {
+        state_version,
+        ..Response::new(state, result, report)
+    })
+}
+
+/// Run ID used by `IdStrategy::RunIndex` when previewing inline items
+const PREVIEW_RUN_ID: &str = "preview";
+
+/// Loads the raw items of a preview request, downloading the dataset if needed.
+#[instrument(skip(request))]
+pub async fn load_preview_items(request: &PreviewRequest) -> anyhow::Result<Vec<Value>> {
+    if let Some(items) = &request.items {
+        return Ok(items.clone());
+    }
+    match (&request.dataset_id, &request.token) {
+        (Some(dataset_id), Some(token)) => {
+            let client = ApiFyClient::new(token);
+            client.download_results(&DatasetId(dataset_id.clone())).await
+        }
+        (Some(_), None) => anyhow::bail!("A token is required to preview a dataset"),
+        (None, _) => anyhow::bail!("Either items or dataset_id is required"),
+    }
+}
+
+/// Runs extraction and state update on raw items, without starting any run.
+#[instrument(skip(items, request), fields(item_count = items.len()))]
+pub fn preview(items: Vec<Value>, request: &PreviewRequest) -> anyhow::Result<Response> {
+    validate_settings(
+        &request.key_mapping,
+        request.state_mapping.as_ref(),
+        &request.extraction,
+        &request.state,
+    )?;
+
+    let mut ctx = extraction_context(&request.state);
+    ctx.run_id = Some(
+        request
+            .dataset_id
+            .clone()
+            .unwrap_or_else(|| PREVIEW_RUN_ID.to_string()),
+    );
+    let Extraction {
+        items: result,
+        report,
+        seen_digests,
+    } = extract_export_items(items, &request.key_mapping, &request.extraction, &ctx)?;
+
+    let ctx = mapping_utils::Context::new().with_seen_digests(seen_digests);
+    let state = update_state_core(&result, &request.state, request.state_mapping.as_ref(), ctx)?;
+
+    Ok(Response::new(state, result, report))
 }
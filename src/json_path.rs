@@ -0,0 +1,107 @@
+use serde_json::Value;
+
+/// One step of a path: an object key or an array index
+#[derive(Debug, Clone, PartialEq)]
+pub enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// Parses a dotted path with optional array indexes, e.g. `author.name` or
+/// `startUrls[0].userData.since`.
+pub fn parse(path: &str) -> Result<Vec<Segment>, String> {
+    let mut segments = Vec::new();
+    for part in path.split('.') {
+        let (key, mut indexes) = match part.find('[') {
+            Some(pos) => (&part[..pos], &part[pos..]),
+            None => (part, ""),
+        };
+        if key.is_empty() && (segments.is_empty() || indexes.is_empty()) {
+            return Err(format!("Invalid path '{}': empty segment", path));
+        }
+        if !key.is_empty() {
+            segments.push(Segment::Key(key.to_string()));
+        }
+        while !indexes.is_empty() {
+            let index = indexes
+                .strip_prefix('[')
+                .and_then(|s| s.split_once(']'))
+                .and_then(|(index, rest)| index.parse().ok().map(|index| (index, rest)));
+            let Some((index, rest)) = index else {
+                return Err(format!("Invalid path '{}': malformed index", path));
+            };
+            segments.push(Segment::Index(index));
+            indexes = rest;
+        }
+    }
+    Ok(segments)
+}
+
+/// Resolves a path inside a JSON value. Malformed paths resolve to nothing.
+pub fn get<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    let segments = parse(path).ok()?;
+    get_segments(value, &segments)
+}
+
+/// Resolves already parsed segments inside a JSON value.
+pub fn get_segments<'a>(value: &'a Value, segments: &[Segment]) -> Option<&'a Value> {
+    segments
+        .iter()
+        .try_fold(value, |current, segment| match segment {
+            Segment::Key(key) => current.get(key),
+            Segment::Index(index) => current.get(index),
+        })
+}
+
+/// Returns the value at already parsed segments, creating missing objects and arrays on
+/// the way. An index may point one past the end of an array to add an element.
+pub fn get_or_insert<'a>(
+    value: &'a mut Value,
+    segments: &[Segment],
+) -> Result<&'a mut Value, String> {
+    segments
+        .iter()
+        .try_fold(value, |current, segment| match segment {
+            Segment::Key(key) => {
+                if current.is_null() {
+                    *current = Value::Object(Default::default());
+                }
+                match current {
+                    Value::Object(map) => Ok(map.entry(key.clone()).or_insert(Value::Null)),
+                    other => Err(format!("Cannot set '{}' on {}", key, kind(other))),
+                }
+            }
+            Segment::Index(index) => {
+                if current.is_null() {
+                    *current = Value::Array(Vec::new());
+                }
+                match current {
+                    Value::Array(array) => {
+                        if *index > array.len() {
+                            return Err(format!(
+                                "Index {} is out of bounds for an array of {} elements",
+                                index,
+                                array.len()
+                            ));
+                        }
+                        if *index == array.len() {
+                            array.push(Value::Null);
+                        }
+                        Ok(&mut array[*index])
+                    }
+                    other => Err(format!("Cannot index {} with [{}]", kind(other), index)),
+                }
+            }
+        })
+}
+
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
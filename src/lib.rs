@@ -3,7 +3,14 @@ pub mod client;
 pub mod dto;
 pub mod extraction;
 pub mod handlers;
+pub mod html_text;
+pub mod input_schema;
 pub mod job;
+pub mod json_path;
 pub mod mapping_utils;
 pub mod metrics;
+pub mod scripting;
+pub mod state_store;
+pub mod template;
+pub mod validation;
 pub mod web_utils;
\ No newline at end of file
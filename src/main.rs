@@ -1,12 +1,28 @@
-use apify_connector::handlers::{get_actor_schema, handle_arbitrary_actor, handle_job, list_actors};
+use std::sync::Arc;
+
+use apify_connector::actors::declarative;
+use apify_connector::handlers::{
+    delete_state, get_actor_schema, get_remote_actor_schema, get_state, handle_actor_validate,
+    handle_arbitrary_actor, handle_job, handle_preview, handle_validate, list_actors,
+    list_functions, put_state,
+};
 use apify_connector::metrics::init_metrics;
+use apify_connector::state_store::{SharedStateStore, StateStore};
 use axum::{
     Router,
+    extract::FromRef,
     routing::{get, post},
 };
 use metrics_exporter_prometheus::PrometheusHandle;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+/// Shared state of the HTTP handlers
+#[derive(Clone, FromRef)]
+struct AppState {
+    metrics: PrometheusHandle,
+    state_store: SharedStateStore,
+}
+
 /// GET /health - Health check endpoint for k8s probes
 async fn health_handler() -> &'static str {
     "ok"
@@ -33,6 +49,22 @@ async fn main() {
     // Initialize Prometheus metrics
     let metrics_handle = init_metrics();
 
+    // Open the state store if a data directory is configured
+    let state_store = StateStore::from_env()
+        .expect("Failed to open the state store")
+        .map(Arc::new);
+    match &state_store {
+        Some(_) => tracing::info!("State store enabled"),
+        None => tracing::info!("State store disabled, set DATA_DIR to enable it"),
+    }
+
+    // Load declarative actors if an actors directory is configured
+    let actors = declarative::init_from_env().expect("Failed to load declarative actors");
+    match actors {
+        Some(count) => tracing::info!(count, "Declarative actors loaded, watching for changes"),
+        None => tracing::info!("No declarative actors, set ACTORS_DIR to load them"),
+    }
+
     // Get port from environment variable or use default
     let port: u16 = std::env::var("PORT")
         .ok()
@@ -43,10 +75,22 @@ async fn main() {
         .route("/health", get(health_handler))
         .route("/actors", get(list_actors))
         .route("/actors/{actor_type}", get(get_actor_schema))
+        .route("/actors/{actor_type}/validate", post(handle_actor_validate))
+        .route("/actors/remote/{*actor_id}", get(get_remote_actor_schema))
+        .route("/functions", get(list_functions))
         .route("/run", post(handle_arbitrary_actor))
+        .route("/preview", post(handle_preview))
+        .route("/validate", post(handle_validate))
+        .route(
+            "/state/{job_name}",
+            get(get_state).put(put_state).delete(delete_state),
+        )
         .route("/{actor_type}", post(handle_job))
         .route("/metrics", get(metrics_handler))
-        .with_state(metrics_handle);
+        .with_state(AppState {
+            metrics: metrics_handle,
+            state_store,
+        });
 
     let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", port))
         .await
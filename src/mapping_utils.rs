@@ -1,62 +1,236 @@
 use std::collections::HashMap;
+use std::fmt::Write;
 
-use chrono::{DateTime, Duration, Utc};
-use rhai::{Engine, Scope};
+use chrono::{DateTime, Utc};
+use rhai::{Array, Dynamic, Map, Scope};
 use serde_json::Value;
 
-use crate::dto::{ExportItem, JobCreation, StateMapping};
+use crate::dto::{ExportItem, JobState, MergeStrategy, StateMapping, StateUpdate};
+use crate::extraction::SEEN_DIGESTS_KEY;
+use crate::json_path;
+use crate::scripting;
+use crate::template::Template;
 
 #[derive(Clone, Debug, Default)]
 pub struct Context {
     pub start: DateTime<Utc>,
+    /// Digests of seen items to remember for the next run (see `DedupeSettings::across_runs`)
+    pub seen_digests: Option<Vec<String>>,
 }
 
 impl Context {
     pub fn new() -> Self {
-        Self { start: Utc::now() }
+        Self {
+            start: Utc::now(),
+            seen_digests: None,
+        }
+    }
+
+    pub fn with_seen_digests(mut self, seen_digests: Option<Vec<String>>) -> Self {
+        self.seen_digests = seen_digests;
+        self
     }
 }
 
-/// Core state update logic that can be used by both typed and arbitrary actor jobs
+/// Converts exported items for the Rhai scope. Dates stay dates so the date helpers apply.
+fn items_to_dynamic(items: &[ExportItem]) -> Array {
+    items
+        .iter()
+        .map(|item| {
+            let mut map = Map::new();
+            map.insert(
+                "id".into(),
+                item.id.clone().map_or(Dynamic::UNIT, Dynamic::from),
+            );
+            map.insert("content".into(), Dynamic::from(item.content.clone()));
+            map.insert("date".into(), Dynamic::from(item.date));
+            let metadata: Map = item
+                .metadata
+                .iter()
+                .map(|(k, v)| (k.into(), Dynamic::from(v.clone())))
+                .collect();
+            map.insert("metadata".into(), Dynamic::from(metadata));
+            Dynamic::from(map)
+        })
+        .collect()
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Core state update logic that can be used by both typed and arbitrary actor jobs.
+///
+/// Literals are stored as is, templates are rendered with [`render_template`] and `keep`
+/// leaves the value untouched. Expressions see `start_date`, the exported `items`, the
+/// previous `state` map and each previous state value as a variable of the same name.
+/// An expression evaluating to `()` (e.g. `max_date(items)` on an empty run) leaves the
+/// state value untouched; any other result is stored as JSON (strings, numbers, arrays,
+/// maps...).
 pub fn update_state_core(
-    _result: &Vec<ExportItem>,
-    state_str: &str,
+    result: &[ExportItem],
+    state: &JobState,
     state_mapping: Option<&Vec<StateMapping>>,
     ctx: Context,
-) -> anyhow::Result<String> {
-    let mut state: HashMap<String, Value> = serde_json::from_str(state_str)?;
-    let mut engine = Engine::new();
+) -> anyhow::Result<JobState> {
+    let previous = serde_json::to_value(state)?;
+    let mut state = state.clone();
     let mut scope = Scope::new();
     scope.push("start_date", ctx.start);
-    engine.register_fn("format_date", |d: DateTime<Utc>, format: &str| {
-        d.format(format).to_string()
-    });
-    engine.register_fn("sub_days", |d: DateTime<Utc>, days: i64| {
-        d - Duration::days(days)
-    });
+    scope.push("items", items_to_dynamic(result));
+    scope.push("state", scripting::to_dynamic(&previous));
+    // Built-in variables take precedence over state keys of the same name
+    for (key, value) in &state {
+        if is_identifier(key) && scope.get(key).is_none() {
+            scope.push_dynamic(key.clone(), scripting::to_dynamic(value));
+        }
+    }
     if let Some(mapping) = state_mapping {
         for m in mapping {
-            let result = if m.update.starts_with("$") {
-                let s = &m.update[1..];
-                let result: String = engine
-                    .eval_with_scope(&mut scope, s)
-                    .map_err(|_e| anyhow::anyhow!("failed to execute update state mapping"))?;
-                result
-            } else {
-                m.from.to_string()
+            let result = match &m.update {
+                StateUpdate::Literal(value) => value.clone(),
+                StateUpdate::Expr(script) => {
+                    let ast = scripting::compile(script)?;
+                    let result: Dynamic = scripting::engine()
+                        .eval_ast_with_scope(&mut scope, &ast)
+                        .map_err(|e| {
+                            anyhow::anyhow!(
+                                "Failed to evaluate state mapping for '{}': {}",
+                                m.from,
+                                e
+                            )
+                        })?;
+                    if result.is_unit() {
+                        continue;
+                    }
+                    scripting::from_dynamic(&result).map_err(|e| {
+                        anyhow::anyhow!("State mapping for '{}' returned {}", m.from, e)
+                    })?
+                }
+                StateUpdate::Template(template) => {
+                    let rendered =
+                        render_template(template, ctx.start, &previous).map_err(|e| {
+                            anyhow::anyhow!(
+                                "Failed to render state mapping for '{}': {}",
+                                m.from,
+                                e
+                            )
+                        })?;
+                    Value::String(rendered)
+                }
+                StateUpdate::Keep => continue,
             };
-            state.insert(m.from.to_string(), Value::String(result));
+            state.insert(m.from.to_string(), result);
         }
     }
 
-    let e = serde_json::to_string(&state)?;
-    Ok(e)
+    if let Some(seen_digests) = ctx.seen_digests {
+        state.insert(SEEN_DIGESTS_KEY.to_string(), Value::from(seen_digests));
+    }
+
+    Ok(state)
 }
 
-pub fn update_state(
-    result: &Vec<ExportItem>,
-    job: &JobCreation,
-    ctx: Context,
+/// Renders a templated state update. Placeholders are `start_date`, `now` or a path in
+/// the previous state, optionally followed by a date format: `{start_date:%Y-%m-%d}`.
+/// Missing state values render as empty text.
+pub fn render_template(
+    template: &str,
+    start: DateTime<Utc>,
+    state: &Value,
 ) -> anyhow::Result<String> {
-    update_state_core(result, &job.state, job.settings.state_mapping.as_ref(), ctx)
+    Template::parse(template)?.render(|placeholder| {
+        let (name, format) = split_format(placeholder);
+        let value = match name {
+            "start_date" => Value::String(start.to_rfc3339()),
+            "now" => Value::String(Utc::now().to_rfc3339()),
+            path => json_path::get(state, path).cloned().unwrap_or(Value::Null),
+        };
+        let Some(format) = format else {
+            return Ok(match value {
+                Value::Null => String::new(),
+                Value::String(s) => s,
+                other => other.to_string(),
+            });
+        };
+        let date = value
+            .as_str()
+            .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+            .ok_or_else(|| anyhow::anyhow!("'{}' is not an RFC 3339 date", name))?;
+        let mut out = String::new();
+        write!(out, "{}", date.format(format))
+            .map_err(|_| anyhow::anyhow!("Invalid date format '{}'", format))?;
+        Ok(out)
+    })
+}
+
+/// Splits a `name:format` placeholder.
+pub fn split_format(placeholder: &str) -> (&str, Option<&str>) {
+    match placeholder.split_once(':') {
+        Some((name, format)) => (name.trim(), Some(format)),
+        None => (placeholder.trim(), None),
+    }
+}
+
+/// Places state values into an actor input body, at the path given by each mapping's
+/// `to` and combined with the existing value according to its merge strategy.
+/// Mappings without `to` only keep a cursor in the state and are skipped.
+pub fn apply_state_mapping(
+    body: &mut HashMap<String, Value>,
+    state: &JobState,
+    state_mapping: Option<&Vec<StateMapping>>,
+) -> anyhow::Result<()> {
+    let Some(mapping) = state_mapping else {
+        return Ok(());
+    };
+    let mut input = Value::Object(body.clone().into_iter().collect());
+    for m in mapping {
+        let (Some(to), Some(value)) = (&m.to, state.get(&m.from)) else {
+            continue;
+        };
+        let segments = json_path::parse(to).map_err(|e| anyhow::anyhow!(e))?;
+        let target = json_path::get_or_insert(&mut input, &segments)
+            .map_err(|e| anyhow::anyhow!("Cannot place state '{}' at '{}': {}", m.from, to, e))?;
+        merge(target, value.clone(), m.merge)
+            .map_err(|e| anyhow::anyhow!("Cannot place state '{}' at '{}': {}", m.from, to, e))?;
+    }
+    if let Value::Object(map) = input {
+        *body = map.into_iter().collect();
+    }
+    Ok(())
+}
+
+fn merge(target: &mut Value, value: Value, strategy: MergeStrategy) -> Result<(), String> {
+    match strategy {
+        MergeStrategy::Replace => *target = value,
+        MergeStrategy::Append => {
+            if target.is_null() {
+                *target = Value::Array(Vec::new());
+            }
+            let Value::Array(array) = target else {
+                return Err("the target is not an array".to_string());
+            };
+            match value {
+                Value::Array(values) => array.extend(values),
+                value => array.push(value),
+            }
+        }
+        MergeStrategy::DeepMerge => deep_merge(target, value),
+    }
+    Ok(())
+}
+
+fn deep_merge(target: &mut Value, value: Value) {
+    match (target, value) {
+        (Value::Object(target), Value::Object(values)) => {
+            for (key, value) in values {
+                deep_merge(target.entry(key).or_insert(Value::Null), value);
+            }
+        }
+        (target, value) => *target = value,
+    }
 }
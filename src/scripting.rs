@@ -0,0 +1,390 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::{Arc, LazyLock, Mutex};
+
+use chrono::{DateTime, Datelike, Days, NaiveDate, NaiveDateTime, TimeDelta, Utc};
+use regex::Regex;
+use rhai::module_resolvers::DummyModuleResolver;
+use rhai::{Array, Dynamic, Engine, EvalAltResult, ImmutableString, Map, AST};
+use serde::Serialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tracing::debug;
+
+use crate::html_text::html_to_text;
+use crate::json_path;
+
+/// Operations a single evaluation may perform before being aborted
+const MAX_OPERATIONS: u64 = 1_000_000;
+/// Nested function calls, which bounds recursion
+const MAX_CALL_LEVELS: usize = 32;
+/// Expression nesting, at global level and inside functions
+const MAX_EXPR_DEPTH: usize = 64;
+const MAX_FUNCTION_EXPR_DEPTH: usize = 32;
+/// Size limits for values built by scripts
+const MAX_STRING_SIZE: usize = 1024 * 1024;
+const MAX_ARRAY_SIZE: usize = 100_000;
+const MAX_MAP_SIZE: usize = 10_000;
+/// Compiled scripts kept in the cache before it is reset
+const AST_CACHE_SIZE: usize = 1024;
+/// Compiled regexes kept in the cache before it is reset
+const REGEX_CACHE_SIZE: usize = 256;
+
+static ENGINE: LazyLock<Engine> = LazyLock::new(build_engine);
+static AST_CACHE: LazyLock<Mutex<HashMap<[u8; 32], Arc<AST>>>> =
+    LazyLock::new(Default::default);
+static REGEX_CACHE: LazyLock<Mutex<HashMap<String, Regex>>> = LazyLock::new(Default::default);
+
+/// Description of a helper function, as listed by `GET /functions`
+#[derive(Debug, Clone, Serialize)]
+pub struct FunctionDoc {
+    pub name: &'static str,
+    pub signature: &'static str,
+    pub description: &'static str,
+}
+
+const fn doc(
+    name: &'static str,
+    signature: &'static str,
+    description: &'static str,
+) -> FunctionDoc {
+    FunctionDoc {
+        name,
+        signature,
+        description,
+    }
+}
+
+/// Helper functions registered by [`new_engine`], on top of the Rhai built-ins
+pub const FUNCTIONS: &[FunctionDoc] = &[
+    doc("now", "now() -> date", "Current time (UTC)"),
+    doc(
+        "parse_date",
+        "parse_date(text, format) -> date",
+        "Parses a date or date-time with a strftime format; dates without a time are midnight UTC",
+    ),
+    doc(
+        "format_date",
+        "format_date(date, format) -> string",
+        "Formats a date with a strftime format",
+    ),
+    doc("add_days", "add_days(date, days) -> date", "Adds days to a date"),
+    doc("sub_days", "sub_days(date, days) -> date", "Subtracts days from a date"),
+    doc("add_hours", "add_hours(date, hours) -> date", "Adds hours to a date"),
+    doc(
+        "add_minutes",
+        "add_minutes(date, minutes) -> date",
+        "Adds minutes to a date",
+    ),
+    doc("start_of_day", "start_of_day(date) -> date", "Midnight of the same day"),
+    doc(
+        "start_of_week",
+        "start_of_week(date) -> date",
+        "Midnight of the Monday of the same week",
+    ),
+    doc(
+        "start_of_month",
+        "start_of_month(date) -> date",
+        "Midnight of the first day of the month",
+    ),
+    doc("to_unix", "to_unix(date) -> int", "Seconds since the Unix epoch"),
+    doc(
+        "from_unix",
+        "from_unix(seconds) -> date",
+        "Date from seconds since the Unix epoch",
+    ),
+    doc(
+        "max",
+        "max(date, date) -> date",
+        "Latest of two dates; a missing date is ignored",
+    ),
+    doc(
+        "min",
+        "min(date, date) -> date",
+        "Earliest of two dates; a missing date is ignored",
+    ),
+    doc("max_date", "max_date(items) -> date", "Latest date of the exported items"),
+    doc("min_date", "min_date(items) -> date", "Earliest date of the exported items"),
+    doc("count", "count(items) -> int", "Number of exported items"),
+    doc(
+        "last_id",
+        "last_id(items) -> string",
+        "ID of the last exported item that has one",
+    ),
+    doc(
+        "regex_match",
+        "regex_match(text, pattern) -> bool",
+        "Whether the text matches the pattern",
+    ),
+    doc(
+        "regex_extract",
+        "regex_extract(text, pattern) -> string",
+        "First capture group of the first match, or the whole match without groups",
+    ),
+    doc(
+        "strip_html",
+        "strip_html(text) -> string",
+        "Removes markup and decodes entities",
+    ),
+    doc("slugify", "slugify(text) -> string", "Lowercase words joined by dashes"),
+    doc("left", "left(text, chars) -> string", "First characters of the text"),
+    doc(
+        "or_default",
+        "or_default(value, default) -> any",
+        "The value, or the default when the value is missing or an empty string",
+    ),
+    doc(
+        "json_get",
+        "json_get(value, path) -> any",
+        "Value at a path such as `cursor.pages[0]` in a map, e.g. `json_get(state, \"cursor.since\")`",
+    ),
+];
+
+/// The Rhai engine shared by state mapping updates, key mapping transforms and filters,
+/// with the connector helper functions registered (see [`FUNCTIONS`]).
+///
+/// Scripts come from callers, so the engine is sandboxed: evaluations are bounded in
+/// operations, call depth and value sizes, and `import`/`eval` are disabled.
+/// Date helpers pass a missing date (`()`) through, so `format_date(max_date(items), ...)`
+/// is `()` when there are no items.
+pub fn engine() -> &'static Engine {
+    &ENGINE
+}
+
+fn build_engine() -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .set_max_operations(MAX_OPERATIONS)
+        .set_max_call_levels(MAX_CALL_LEVELS)
+        .set_max_expr_depths(MAX_EXPR_DEPTH, MAX_FUNCTION_EXPR_DEPTH)
+        .set_max_string_size(MAX_STRING_SIZE)
+        .set_max_array_size(MAX_ARRAY_SIZE)
+        .set_max_map_size(MAX_MAP_SIZE)
+        .set_module_resolver(DummyModuleResolver::new())
+        .disable_symbol("import")
+        .disable_symbol("eval")
+        .on_print(|text| debug!(target: "rhai", "{}", text))
+        .on_debug(|text, _, _| debug!(target: "rhai", "{}", text));
+
+    engine.register_fn("now", Utc::now);
+    engine.register_fn(
+        "parse_date",
+        |text: &str, format: &str| -> Result<DateTime<Utc>, Box<EvalAltResult>> {
+            parse_date(text, format)
+                .ok_or_else(|| format!("Cannot parse '{}' with format '{}'", text, format).into())
+        },
+    );
+    engine.register_fn(
+        "format_date",
+        |d: DateTime<Utc>, format: &str| -> Result<String, Box<EvalAltResult>> {
+            let mut out = String::new();
+            write!(out, "{}", d.format(format))
+                .map_err(|_| format!("Invalid date format '{}'", format))?;
+            Ok(out)
+        },
+    );
+    engine.register_fn("format_date", |_: (), _: &str| ());
+    engine.register_fn("add_days", |d: DateTime<Utc>, days: i64| {
+        shift(d, TimeDelta::try_days(days), days, "days")
+    });
+    engine.register_fn("sub_days", |d: DateTime<Utc>, days: i64| {
+        shift(
+            d,
+            days.checked_neg().and_then(TimeDelta::try_days),
+            days,
+            "days",
+        )
+    });
+    engine.register_fn("add_hours", |d: DateTime<Utc>, hours: i64| {
+        shift(d, TimeDelta::try_hours(hours), hours, "hours")
+    });
+    engine.register_fn("add_minutes", |d: DateTime<Utc>, minutes: i64| {
+        shift(d, TimeDelta::try_minutes(minutes), minutes, "minutes")
+    });
+    for name in ["add_days", "sub_days", "add_hours", "add_minutes"] {
+        engine.register_fn(name, |_: (), _: i64| ());
+    }
+    engine.register_fn("start_of_day", |d: DateTime<Utc>| midnight(d.date_naive()));
+    engine.register_fn(
+        "start_of_week",
+        |d: DateTime<Utc>| -> Result<DateTime<Utc>, Box<EvalAltResult>> {
+            let days = d.weekday().num_days_from_monday() as u64;
+            d.date_naive()
+                .checked_sub_days(Days::new(days))
+                .map(midnight)
+                .ok_or_else(|| format!("The week of {} is out of range", d).into())
+        },
+    );
+    engine.register_fn("start_of_month", |d: DateTime<Utc>| {
+        midnight(d.date_naive().with_day(1).unwrap_or(d.date_naive()))
+    });
+    engine.register_fn("to_unix", |d: DateTime<Utc>| d.timestamp());
+    for name in ["start_of_day", "start_of_week", "start_of_month", "to_unix"] {
+        engine.register_fn(name, |_: ()| ());
+    }
+    engine.register_fn(
+        "from_unix",
+        |seconds: i64| -> Result<DateTime<Utc>, Box<EvalAltResult>> {
+            DateTime::from_timestamp(seconds, 0)
+                .ok_or_else(|| format!("Timestamp {} is out of range", seconds).into())
+        },
+    );
+    engine.register_fn("max", |a: DateTime<Utc>, b: DateTime<Utc>| a.max(b));
+    engine.register_fn("min", |a: DateTime<Utc>, b: DateTime<Utc>| a.min(b));
+    for name in ["max", "min"] {
+        engine.register_fn(name, |d: DateTime<Utc>, _: ()| d);
+        engine.register_fn(name, |_: (), d: DateTime<Utc>| d);
+    }
+
+    // Aggregates over exported items, for cursors in state mappings
+    engine.register_fn("max_date", |items: &mut Array| {
+        item_dates(items).max().map_or(Dynamic::UNIT, Dynamic::from)
+    });
+    engine.register_fn("min_date", |items: &mut Array| {
+        item_dates(items).min().map_or(Dynamic::UNIT, Dynamic::from)
+    });
+    engine.register_fn("count", |items: &mut Array| items.len() as i64);
+    engine.register_fn("last_id", |items: &mut Array| {
+        items
+            .iter()
+            .rev()
+            .find_map(|item| item_field(item, "id").filter(|id| id.is_string()))
+            .unwrap_or(Dynamic::UNIT)
+    });
+
+    engine.register_fn("regex_match", |text: &str, pattern: &str| {
+        Ok::<_, Box<EvalAltResult>>(compile_regex(pattern)?.is_match(text))
+    });
+    engine.register_fn("regex_extract", |text: &str, pattern: &str| {
+        let re = compile_regex(pattern)?;
+        // First capture group if the pattern has one, whole match otherwise
+        let extracted = re.captures(text).and_then(|caps| {
+            caps.get(1)
+                .or_else(|| caps.get(0))
+                .map(|m| Dynamic::from(m.as_str().to_string()))
+        });
+        Ok::<_, Box<EvalAltResult>>(extracted.unwrap_or(Dynamic::UNIT))
+    });
+    engine.register_fn("strip_html", |text: &str| html_to_text(text, false));
+    engine.register_fn("slugify", slugify);
+    engine.register_fn("left", |text: &str, chars: i64| {
+        text.chars().take(chars.max(0) as usize).collect::<String>()
+    });
+    engine.register_fn("or_default", |value: Dynamic, default: Dynamic| {
+        let empty = value.is_unit()
+            || value
+                .read_lock::<ImmutableString>()
+                .is_some_and(|s| s.is_empty());
+        if empty {
+            default
+        } else {
+            value
+        }
+    });
+    engine.register_fn(
+        "json_get",
+        |value: Dynamic, path: &str| -> Result<Dynamic, Box<EvalAltResult>> {
+            let segments = json_path::parse(path)?;
+            let json = from_dynamic(&value).map_err(|e| e.to_string())?;
+            Ok(json_path::get_segments(&json, &segments).map_or(Dynamic::UNIT, to_dynamic))
+        },
+    );
+    engine
+}
+
+/// Moves a date by a duration, failing instead of overflowing
+fn shift(
+    d: DateTime<Utc>,
+    delta: Option<TimeDelta>,
+    amount: i64,
+    unit: &str,
+) -> Result<DateTime<Utc>, Box<EvalAltResult>> {
+    delta
+        .and_then(|delta| d.checked_add_signed(delta))
+        .ok_or_else(|| format!("Moving {} by {} {} is out of range", d, amount, unit).into())
+}
+
+fn midnight(date: NaiveDate) -> DateTime<Utc> {
+    date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc()
+}
+
+fn parse_date(text: &str, format: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_str(text, format)
+        .map(|d| d.with_timezone(&Utc))
+        .or_else(|_| NaiveDateTime::parse_from_str(text, format).map(|d| d.and_utc()))
+        .or_else(|_| NaiveDate::parse_from_str(text, format).map(midnight))
+        .ok()
+}
+
+fn slugify(text: &str) -> String {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+fn item_field(item: &Dynamic, field: &str) -> Option<Dynamic> {
+    item.read_lock::<Map>()?.get(field).cloned()
+}
+
+fn item_dates(items: &Array) -> impl Iterator<Item = DateTime<Utc>> + '_ {
+    items
+        .iter()
+        .filter_map(|item| item_field(item, "date")?.try_cast::<DateTime<Utc>>())
+}
+
+/// Compiles a regex, cached by pattern as transforms run it on every item
+fn compile_regex(pattern: &str) -> Result<Regex, Box<EvalAltResult>> {
+    let mut cache = REGEX_CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(re) = cache.get(pattern) {
+        return Ok(re.clone());
+    }
+
+    let re = Regex::new(pattern).map_err(|e| format!("Invalid regex '{}': {}", pattern, e))?;
+    if cache.len() >= REGEX_CACHE_SIZE {
+        cache.clear();
+    }
+    cache.insert(pattern.to_string(), re.clone());
+    Ok(re)
+}
+
+/// Compiles a script with the shared engine, reporting syntax errors with their position.
+///
+/// Compiled scripts are cached by their SHA-256, as the same mappings come with every run.
+pub fn compile(script: &str) -> anyhow::Result<Arc<AST>> {
+    let key: [u8; 32] = Sha256::digest(script.as_bytes()).into();
+    if let Some(ast) = ast_cache().get(&key) {
+        return Ok(ast.clone());
+    }
+
+    let ast = Arc::new(
+        engine()
+            .compile(script)
+            .map_err(|e| anyhow::anyhow!("Invalid expression '{}': {}", script, e))?,
+    );
+    let mut cache = ast_cache();
+    if cache.len() >= AST_CACHE_SIZE {
+        cache.clear();
+    }
+    cache.insert(key, ast.clone());
+    Ok(ast)
+}
+
+fn ast_cache() -> std::sync::MutexGuard<'static, HashMap<[u8; 32], Arc<AST>>> {
+    AST_CACHE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Converts a JSON value into a Rhai value (objects become maps, arrays become arrays).
+pub fn to_dynamic(value: &Value) -> Dynamic {
+    rhai::serde::to_dynamic(value).unwrap_or(Dynamic::UNIT)
+}
+
+/// Converts a Rhai value back into JSON. Dates are rendered as RFC 3339 strings.
+pub fn from_dynamic(value: &Dynamic) -> anyhow::Result<Value> {
+    if let Some(date) = value.clone().try_cast::<DateTime<Utc>>() {
+        return Ok(Value::String(date.to_rfc3339()));
+    }
+    rhai::serde::from_dynamic(value)
+        .map_err(|e| anyhow::anyhow!("Cannot convert {} to JSON: {}", value.type_name(), e))
+}
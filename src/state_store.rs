@@ -0,0 +1,239 @@
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::debug;
+
+use crate::dto::JobState;
+
+/// Maximum length of a job name
+const MAX_JOB_NAME_LENGTH: usize = 128;
+/// File locked around every access, in the store directory
+const LOCK_FILE: &str = ".lock";
+
+/// Makes temporary file names unique within this process
+static TMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// State saved for a job, with a version incremented on every write
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredState {
+    pub job_name: String,
+    pub version: u64,
+    pub state: JobState,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Error)]
+pub enum StoreError {
+    #[error(
+        "Invalid job name '{0}': use 1 to 128 letters, digits, '-', '_' or '.', not starting with '.'"
+    )]
+    InvalidJobName(String),
+
+    #[error("State of job '{job_name}' is at version {actual}, expected version {expected}")]
+    Conflict {
+        job_name: String,
+        expected: u64,
+        actual: u64,
+    },
+
+    #[error("State store I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Corrupted state file: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// State store shared by the handlers, `None` when disabled
+pub type SharedStateStore = Option<Arc<StateStore>>;
+
+/// File-backed job state store: one JSON file per job name.
+///
+/// Writes go to a temporary file renamed over the previous one, so a crash never leaves
+/// a half-written state. Versions give optimistic concurrency: a write expecting
+/// version `n` fails with [`StoreError::Conflict`] if another write happened since.
+/// A job that has never been saved is at version 0.
+///
+/// Every access holds a lock on a file in the directory, so replicas sharing
+/// `DATA_DIR` see each other's writes, as long as the file system supports `flock`
+/// (local disks do, some network file systems don't).
+///
+/// Methods do blocking file I/O; async code uses the `_async` variants, which run them
+/// on the blocking thread pool.
+#[derive(Debug, Clone)]
+pub struct StateStore {
+    dir: PathBuf,
+}
+
+impl StateStore {
+    pub fn open(dir: impl Into<PathBuf>) -> Result<Self, StoreError> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    /// Opens the store under `$DATA_DIR/state`, if `DATA_DIR` is set.
+    pub fn from_env() -> Result<Option<Self>, StoreError> {
+        match std::env::var_os("DATA_DIR") {
+            Some(dir) => Self::open(Path::new(&dir).join("state")).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get(&self, job_name: &str) -> Result<Option<StoredState>, StoreError> {
+        let path = self.path(job_name)?;
+        let _lock = self.lock(false)?;
+        read(&path)
+    }
+
+    /// Saves the state of a job. With `expected_version`, the write only happens if the
+    /// stored version still matches.
+    pub fn put(
+        &self,
+        job_name: &str,
+        state: JobState,
+        expected_version: Option<u64>,
+    ) -> Result<StoredState, StoreError> {
+        let path = self.path(job_name)?;
+        let _lock = self.lock(true)?;
+
+        let actual = read(&path)?.map_or(0, |stored| stored.version);
+        check_version(job_name, expected_version, actual)?;
+
+        let stored = StoredState {
+            job_name: job_name.to_string(),
+            version: actual + 1,
+            state,
+            updated_at: Utc::now(),
+        };
+        write_atomically(&path, &serde_json::to_vec_pretty(&stored)?)?;
+        debug!(job_name, version = stored.version, "Saved job state");
+        Ok(stored)
+    }
+
+    /// Deletes the state of a job, returning whether there was one.
+    pub fn delete(
+        &self,
+        job_name: &str,
+        expected_version: Option<u64>,
+    ) -> Result<bool, StoreError> {
+        let path = self.path(job_name)?;
+        let _lock = self.lock(true)?;
+
+        let Some(stored) = read(&path)? else {
+            return Ok(false);
+        };
+        check_version(job_name, expected_version, stored.version)?;
+        fs::remove_file(&path)?;
+        debug!(job_name, "Deleted job state");
+        Ok(true)
+    }
+
+    /// [`StateStore::get`], on the blocking thread pool
+    pub async fn get_async(&self, job_name: &str) -> Result<Option<StoredState>, StoreError> {
+        let job_name = job_name.to_string();
+        self.spawn(move |store| store.get(&job_name)).await
+    }
+
+    /// [`StateStore::put`], on the blocking thread pool
+    pub async fn put_async(
+        &self,
+        job_name: &str,
+        state: JobState,
+        expected_version: Option<u64>,
+    ) -> Result<StoredState, StoreError> {
+        let job_name = job_name.to_string();
+        self.spawn(move |store| store.put(&job_name, state, expected_version))
+            .await
+    }
+
+    /// [`StateStore::delete`], on the blocking thread pool
+    pub async fn delete_async(
+        &self,
+        job_name: &str,
+        expected_version: Option<u64>,
+    ) -> Result<bool, StoreError> {
+        let job_name = job_name.to_string();
+        self.spawn(move |store| store.delete(&job_name, expected_version))
+            .await
+    }
+
+    async fn spawn<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&StateStore) -> Result<T, StoreError> + Send + 'static,
+    ) -> Result<T, StoreError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || f(&store))
+            .await
+            .map_err(|e| StoreError::Io(std::io::Error::other(e)))?
+    }
+
+    /// Locks the store, exclusively for read-modify-write cycles. The lock is released
+    /// when the returned file is dropped.
+    fn lock(&self, exclusive: bool) -> Result<File, StoreError> {
+        let file = File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.dir.join(LOCK_FILE))?;
+        if exclusive {
+            file.lock()?;
+        } else {
+            file.lock_shared()?;
+        }
+        Ok(file)
+    }
+
+    fn path(&self, job_name: &str) -> Result<PathBuf, StoreError> {
+        let valid = !job_name.is_empty()
+            && job_name.len() <= MAX_JOB_NAME_LENGTH
+            && !job_name.starts_with('.')
+            && job_name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        if !valid {
+            return Err(StoreError::InvalidJobName(job_name.to_string()));
+        }
+        Ok(self.dir.join(format!("{}.json", job_name)))
+    }
+}
+
+fn check_version(job_name: &str, expected: Option<u64>, actual: u64) -> Result<(), StoreError> {
+    match expected {
+        Some(expected) if expected != actual => Err(StoreError::Conflict {
+            job_name: job_name.to_string(),
+            expected,
+            actual,
+        }),
+        _ => Ok(()),
+    }
+}
+
+fn read(path: &Path) -> Result<Option<StoredState>, StoreError> {
+    match fs::read(path) {
+        Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn write_atomically(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    // Named after the target, the process and a counter, so concurrent writers never
+    // share one; the leading '.' keeps it apart from job names
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let tmp = path.with_file_name(format!(
+        ".{}.{}.{}.tmp",
+        file_name,
+        std::process::id(),
+        TMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let mut file = fs::File::create(&tmp)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)
+}
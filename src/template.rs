@@ -0,0 +1,85 @@
+/// A piece of a parsed template
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Literal(String),
+    Placeholder(String),
+}
+
+/// A text template with `{...}` placeholders; `{{` and `}}` stand for literal braces.
+///
+/// The meaning of a placeholder is left to the caller, which resolves it when rendering.
+#[derive(Debug, Clone)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+impl Template {
+    pub fn parse(template: &str) -> anyhow::Result<Self> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut placeholder = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some('{') | None => anyhow::bail!(
+                                "Unclosed placeholder '{{{}' in template '{}'",
+                                placeholder,
+                                template
+                            ),
+                            Some(c) => placeholder.push(c),
+                        }
+                    }
+                    if placeholder.trim().is_empty() {
+                        anyhow::bail!("Empty placeholder in template '{}'", template);
+                    }
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Placeholder(placeholder));
+                }
+                '}' => anyhow::bail!("Unmatched '}}' in template '{}'", template),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        Ok(Self { parts })
+    }
+
+    /// Placeholder contents, in order of appearance
+    pub fn placeholders(&self) -> impl Iterator<Item = &str> {
+        self.parts.iter().filter_map(|part| match part {
+            Part::Placeholder(p) => Some(p.as_str()),
+            Part::Literal(_) => None,
+        })
+    }
+
+    /// Renders the template, resolving every placeholder with `resolve`.
+    pub fn render<F>(&self, mut resolve: F) -> anyhow::Result<String>
+    where
+        F: FnMut(&str) -> anyhow::Result<String>,
+    {
+        let mut out = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(s) => out.push_str(s),
+                Part::Placeholder(p) => out.push_str(&resolve(p)?),
+            }
+        }
+        Ok(out)
+    }
+}
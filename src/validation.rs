@@ -0,0 +1,203 @@
+use chrono::format::{Item, StrftimeItems};
+use serde::Serialize;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::dto::{DataKind, ExtractionSettings, IdStrategy, KeyMapping, StateMapping, StateUpdate};
+use crate::json_path;
+use crate::mapping_utils;
+use crate::scripting;
+use crate::template::Template;
+
+/// A problem found in a specific field of a request
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldError {
+    /// Location of the field, e.g. `key_mapping[1].kind`
+    pub field: String,
+    pub message: String,
+}
+
+/// Every problem found while validating a request
+#[derive(Debug, Error)]
+#[error("Invalid request: {}", summary(.0))]
+pub struct ValidationErrors(pub Vec<FieldError>);
+
+impl ValidationErrors {
+    /// A single error on one field
+    pub fn field(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Self(vec![FieldError {
+            field: field.into(),
+            message: message.into(),
+        }])
+    }
+}
+
+fn summary(errors: &[FieldError]) -> String {
+    errors
+        .iter()
+        .map(|e| format!("{}: {}", e.field, e.message))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Collects field errors before turning them into a result
+#[derive(Default)]
+struct Collector(Vec<FieldError>);
+
+impl Collector {
+    fn push(&mut self, field: impl Into<String>, message: impl Into<String>) {
+        self.0.push(FieldError {
+            field: field.into(),
+            message: message.into(),
+        });
+    }
+
+    fn into_result(self) -> Result<(), ValidationErrors> {
+        if self.0.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationErrors(self.0))
+        }
+    }
+}
+
+/// Checks key mappings, state mappings and extraction settings before anything runs:
+/// required targets, kind compatibility, date formats and Rhai syntax.
+pub fn validate_mappings(
+    key_mapping: &[KeyMapping],
+    state_mapping: Option<&Vec<StateMapping>>,
+    extraction: &ExtractionSettings,
+) -> Result<(), ValidationErrors> {
+    let mut errors = Collector::default();
+
+    for (i, mapping) in key_mapping.iter().enumerate() {
+        validate_key_mapping(&format!("key_mapping[{}]", i), mapping, &mut errors);
+    }
+
+    let targets = |to: &str| key_mapping.iter().any(|m| m.to == to);
+    if !targets("content") && extraction.content_template.is_none() {
+        errors.push(
+            "key_mapping",
+            "No mapping targets 'content' and no content_template is set, every item would be dropped",
+        );
+    }
+    if !targets("date") {
+        errors.push(
+            "key_mapping",
+            "No mapping targets 'date', every item would be dropped",
+        );
+    }
+
+    for (i, mapping) in state_mapping.into_iter().flatten().enumerate() {
+        let field = format!("state_mapping[{}]", i);
+        if mapping.from.is_empty() {
+            errors.push(format!("{}.from", field), "State key cannot be empty");
+        }
+        if let Some(Err(e)) = mapping.to.as_deref().map(json_path::parse) {
+            errors.push(format!("{}.to", field), e);
+        }
+        if let Err(message) = validate_state_update(&mapping.update) {
+            errors.push(format!("{}.update", field), message);
+        }
+    }
+
+    validate_extraction(extraction, &mut errors);
+    errors.into_result()
+}
+
+fn validate_state_update(update: &StateUpdate) -> Result<(), String> {
+    match update {
+        StateUpdate::Expr(script) => scripting::compile(script)
+            .map(|_| ())
+            .map_err(|e| e.to_string()),
+        StateUpdate::Template(template) => {
+            let template = Template::parse(template).map_err(|e| e.to_string())?;
+            for placeholder in template.placeholders() {
+                if let (_, Some(format)) = mapping_utils::split_format(placeholder)
+                    && StrftimeItems::new(format).any(|item| item == Item::Error)
+                {
+                    return Err(format!("Invalid date format '{}'", format));
+                }
+            }
+            Ok(())
+        }
+        StateUpdate::Literal(_) | StateUpdate::Keep => Ok(()),
+    }
+}
+
+fn validate_key_mapping(field: &str, mapping: &KeyMapping, errors: &mut Collector) {
+    if mapping.from.is_empty() || mapping.from.iter().any(String::is_empty) {
+        errors.push(format!("{}.from", field), "Source fields cannot be empty");
+    }
+    if mapping.to.is_empty() {
+        errors.push(format!("{}.to", field), "Target cannot be empty");
+    }
+
+    match (&mapping.kind, mapping.to.as_str()) {
+        (DataKind::Date { format }, _) => {
+            if StrftimeItems::new(format).any(|item| item == Item::Error) {
+                errors.push(
+                    format!("{}.kind", field),
+                    format!("Invalid date format '{}'", format),
+                );
+            } else if mapping.to == "date"
+                && let Some(default) = &mapping.default
+            {
+                let valid = default.as_str().is_some_and(|s| {
+                    s == "now" || chrono::NaiveDate::parse_from_str(s, format).is_ok()
+                });
+                if !valid {
+                    errors.push(
+                        format!("{}.default", field),
+                        format!("Default must be \"now\" or a date matching '{}'", format),
+                    );
+                }
+            }
+        }
+        (_, "date") => errors.push(
+            format!("{}.kind", field),
+            "The 'date' target requires a Date kind with a format",
+        ),
+        _ => {}
+    }
+
+    if let Some(script) = &mapping.transform
+        && let Err(e) = scripting::compile(script)
+    {
+        errors.push(format!("{}.transform", field), e.to_string());
+    }
+}
+
+fn validate_extraction(extraction: &ExtractionSettings, errors: &mut Collector) {
+    if let Some(strict) = &extraction.strict
+        && !(0.0..=1.0).contains(&strict.max_drop_ratio)
+    {
+        errors.push("strict.max_drop_ratio", "Must be between 0.0 and 1.0");
+    }
+    if let Some(script) = &extraction.filter
+        && let Err(e) = scripting::compile(script)
+    {
+        errors.push("filter", e.to_string());
+    }
+    if let Some(dedupe) = &extraction.dedupe
+        && dedupe.max_seen == 0
+    {
+        errors.push("dedupe.max_seen", "Must be greater than 0");
+    }
+    if let Some(IdStrategy::UuidV5 {
+        namespace: Some(namespace),
+        ..
+    }) = &extraction.id_strategy
+        && Uuid::parse_str(namespace).is_err()
+    {
+        errors.push(
+            "id_strategy.UuidV5.namespace",
+            format!("Invalid UUID namespace '{}'", namespace),
+        );
+    }
+    if let Some(template) = &extraction.content_template
+        && let Err(e) = Template::parse(template)
+    {
+        errors.push("content_template", e.to_string());
+    }
+}
@@ -5,9 +5,17 @@ use axum::{
 use serde::Serialize;
 use thiserror::Error;
 
+use crate::input_schema::{SchemaErrors, SchemaViolation};
+use crate::state_store::StoreError;
+use crate::validation::{FieldError, ValidationErrors};
+
 #[derive(Debug, Serialize)]
 struct ErrorResponse {
     error: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    errors: Vec<FieldError>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    violations: Vec<SchemaViolation>,
 }
 
 #[derive(Debug, Error)]
@@ -18,11 +26,20 @@ pub enum AppError {
     #[error("{0}")]
     NotFound(String),
 
+    #[error("{0}")]
+    Conflict(String),
+
     #[error("{0}")]
     BadGateway(String),
 
     #[error("{0}")]
     Internal(String),
+
+    #[error(transparent)]
+    Validation(#[from] ValidationErrors),
+
+    #[error(transparent)]
+    InvalidInput(#[from] SchemaErrors),
 }
 
 impl AppError {
@@ -34,6 +51,10 @@ impl AppError {
         Self::NotFound(msg.into())
     }
 
+    pub fn conflict(msg: impl Into<String>) -> Self {
+        Self::Conflict(msg.into())
+    }
+
     pub fn bad_gateway(msg: impl Into<String>) -> Self {
         Self::BadGateway(msg.into())
     }
@@ -41,16 +62,64 @@ impl AppError {
     pub fn internal(msg: impl Into<String>) -> Self {
         Self::Internal(msg.into())
     }
-}
 
-impl IntoResponse for AppError {
-    fn into_response(self) -> Response {
-        let status = match &self {
-            AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+    /// Maps a job error to a response: validation failures become a 400 with field
+    /// errors, actor inputs that break their schema a 422 with the violations, state
+    /// store failures keep their own status, anything else goes through `fallback`.
+    pub fn from_job_error(e: anyhow::Error, fallback: fn(String) -> Self) -> Self {
+        let e = match e.downcast::<ValidationErrors>() {
+            Ok(errors) => return Self::Validation(errors),
+            Err(e) => e,
+        };
+        let e = match e.downcast::<SchemaErrors>() {
+            Ok(errors) => return Self::InvalidInput(errors),
+            Err(e) => e,
+        };
+        match e.downcast::<StoreError>() {
+            Ok(e) => e.into(),
+            Err(e) => fallback(format!("{:#}", e)),
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AppError::BadRequest(_) | AppError::Validation(_) => StatusCode::BAD_REQUEST,
+            AppError::InvalidInput(_) => StatusCode::UNPROCESSABLE_ENTITY,
             AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
             AppError::BadGateway(_) => StatusCode::BAD_GATEWAY,
             AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl From<StoreError> for AppError {
+    fn from(e: StoreError) -> Self {
+        match e {
+            StoreError::InvalidJobName(_) => Self::bad_request(e.to_string()),
+            StoreError::Conflict { .. } => Self::conflict(e.to_string()),
+            StoreError::Io(_) | StoreError::Json(_) => Self::internal(e.to_string()),
+        }
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        let error = self.to_string();
+        let (errors, violations) = match self {
+            AppError::Validation(ValidationErrors(errors)) => (errors, Vec::new()),
+            AppError::InvalidInput(SchemaErrors { violations, .. }) => (Vec::new(), violations),
+            _ => (Vec::new(), Vec::new()),
         };
-        (status, Json(ErrorResponse { error: self.to_string() })).into_response()
+        (
+            status,
+            Json(ErrorResponse {
+                error,
+                errors,
+                violations,
+            }),
+        )
+            .into_response()
     }
 }
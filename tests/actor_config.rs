@@ -0,0 +1,346 @@
+use apify_connector::actors::{find_actor, typed_actor_types, ActorConfig};
+use apify_connector::dto::{ExtractionSettings, JobState, KeyMapping, StateMapping};
+use apify_connector::extraction::{extract_export_items, ExtractionContext};
+use apify_connector::validation::{validate_mappings, ValidationErrors};
+use serde_json::{json, Value};
+
+fn config(actor_type: &str, config: Value) -> ActorConfig {
+    ActorConfig::from_type_and_config(actor_type, config).unwrap()
+}
+
+fn mappings(mappings: Value) -> Vec<StateMapping> {
+    serde_json::from_value(mappings).unwrap()
+}
+
+fn state(value: Value) -> JobState {
+    serde_json::from_value(value).unwrap()
+}
+
+fn fields(errors: &ValidationErrors) -> Vec<&str> {
+    errors.0.iter().map(|e| e.field.as_str()).collect()
+}
+
+#[test]
+fn test_state_is_applied_to_typed_config() {
+    let config = config(
+        "google_search",
+        json!({ "queries": ["rust"], "maxResults": 10 }),
+    );
+    let mapping = mappings(json!([
+        { "from": "max", "to": "maxResults", "update": { "keep": true } },
+        { "from": "query", "to": "queries", "update": { "keep": true }, "merge": "Append" }
+    ]));
+
+    let body = config
+        .body_with_state(
+            &state(json!({ "max": 50, "query": "serde" })),
+            Some(&mapping),
+        )
+        .unwrap();
+
+    assert_eq!(body["maxResults"], 50);
+    assert_eq!(body["queries"], json!(["rust", "serde"]));
+}
+
+#[test]
+fn test_state_with_wrong_type_is_rejected() {
+    let config = config(
+        "web_scraper",
+        json!({ "startUrls": ["https://example.com"] }),
+    );
+    let mapping = mappings(json!([
+        { "from": "pages", "to": "maxPages", "update": { "keep": true } }
+    ]));
+
+    let errors = config
+        .body_with_state(&state(json!({ "pages": "many" })), Some(&mapping))
+        .unwrap_err();
+
+    assert_eq!(fields(&errors), vec!["state_mapping"]);
+    assert!(errors.0[0].message.contains("Invalid web_scraper config"));
+}
+
+#[test]
+fn test_state_is_validated_like_the_config() {
+    let config = config("google_search", json!({ "queries": ["rust"] }));
+    let mapping = mappings(json!([
+        { "from": "max", "to": "maxResults", "update": { "keep": true } }
+    ]));
+
+    let errors = config
+        .body_with_state(&state(json!({ "max": 500 })), Some(&mapping))
+        .unwrap_err();
+
+    assert!(errors.0[0]
+        .message
+        .contains("max_results must be between 1 and 100"));
+}
+
+#[test]
+fn test_state_is_placed_into_the_apify_input() {
+    let config = config(
+        "tripadvisor",
+        json!({ "url": "https://www.tripadvisor.com/x" }),
+    );
+    let mapping = mappings(json!([
+        { "from": "since", "to": "startUrls[0].userData.since", "update": { "keep": true } },
+        { "from": "max", "to": "maxReviews", "update": { "keep": true } }
+    ]));
+
+    let body = config
+        .body_with_state(
+            &state(json!({ "since": "2024-01-15", "max": 20 })),
+            Some(&mapping),
+        )
+        .unwrap();
+
+    assert_eq!(
+        body["startUrls"],
+        json!([{
+            "url": "https://www.tripadvisor.com/x",
+            "method": "GET",
+            "userData": { "since": "2024-01-15" }
+        }])
+    );
+    assert_eq!(body["maxReviews"], 20);
+}
+
+#[test]
+fn test_renamed_config_fields_rebuild_the_apify_input() {
+    let config = config(
+        "tripadvisor",
+        json!({ "url": "https://www.tripadvisor.com/x" }),
+    );
+    let mapping = mappings(json!([
+        { "from": "since", "to": "startUrls[0].userData.since", "update": { "keep": true } },
+        { "from": "next", "to": "url", "update": { "keep": true } }
+    ]));
+
+    let body = config
+        .body_with_state(
+            &state(json!({ "since": "2024-01-15", "next": "https://www.tripadvisor.com/y" })),
+            Some(&mapping),
+        )
+        .unwrap();
+
+    assert_eq!(body["startUrls"][0]["url"], "https://www.tripadvisor.com/y");
+    assert_eq!(body["startUrls"][0]["userData"]["since"], "2024-01-15");
+    assert!(!body.contains_key("url"));
+}
+
+#[test]
+fn test_state_targets_must_be_fields_or_input_keys() {
+    let config = config(
+        "tripadvisor",
+        json!({ "url": "https://www.tripadvisor.com/x" }),
+    );
+    let mapping = mappings(json!([
+        { "from": "max", "to": "maxReviews", "update": { "keep": true } },
+        { "from": "max", "to": "maxReviewz", "update": { "keep": true } },
+        { "from": "since", "to": "options.since", "update": { "keep": true } }
+    ]));
+
+    let errors = config
+        .body_with_state(&state(json!({ "max": 20 })), Some(&mapping))
+        .unwrap_err();
+
+    assert_eq!(
+        fields(&errors),
+        vec!["state_mapping[1].to", "state_mapping[2].to"]
+    );
+    assert!(errors.0[0]
+        .message
+        .contains("'maxReviewz' is not a field of the tripadvisor config"));
+}
+
+#[test]
+fn test_state_without_a_target_is_only_kept_in_state() {
+    let config = config(
+        "web_scraper",
+        json!({ "startUrls": ["https://example.com"] }),
+    );
+    let mapping = mappings(json!([
+        { "from": "last_date", "update": "$format_date(start_date, \"%Y-%m-%d\")" },
+        { "from": "page", "to": null, "update": { "keep": true } }
+    ]));
+
+    let body = config
+        .body_with_state(
+            &state(json!({ "last_date": "2024-01-15", "page": 2 })),
+            Some(&mapping),
+        )
+        .unwrap();
+
+    assert_eq!(
+        body,
+        config.to_body().unwrap(),
+        "cursor-only state must not reach the input"
+    );
+}
+
+#[test]
+fn test_typed_actors_are_registered() {
+    assert_eq!(
+        typed_actor_types(),
+        vec!["google_search", "instagram", "tripadvisor", "web_scraper"]
+    );
+
+    let actor = find_actor("instagram").unwrap();
+    assert_eq!(actor.actor_type(), "instagram");
+    assert!(find_actor("unknown").is_none());
+}
+
+#[test]
+fn test_typed_actor_builds_its_own_body() {
+    let config = config(
+        "tripadvisor",
+        json!({ "url": "https://www.tripadvisor.com/Hotel_Review-x", "maxReviews": 5 }),
+    );
+
+    let body = config.to_body().unwrap();
+
+    assert_eq!(
+        body["startUrls"],
+        json!([{ "url": "https://www.tripadvisor.com/Hotel_Review-x", "method": "GET" }])
+    );
+    assert!(!body.contains_key("url"));
+    assert_eq!(body["maxReviews"], json!(5));
+}
+
+#[test]
+fn test_default_key_mappings_extract_actor_output() {
+    let cases = [
+        (
+            "tripadvisor",
+            json!({ "url": "https://www.tripadvisor.com/Hotel_Review-x" }),
+            json!({ "id": "42", "text": "Great stay", "publishedDate": "2024-03-02", "title": "Nice" }),
+        ),
+        (
+            "instagram",
+            json!({ "usernames": ["rust"] }),
+            json!({ "id": "7", "caption": "New release", "timestamp": "2024-03-02T10:30:00.000Z", "ownerUsername": "rust" }),
+        ),
+        (
+            "google_search",
+            json!({ "queries": ["rust"] }),
+            json!({ "url": "https://google.com/search?q=rust", "organicResults": [
+                { "title": "Rust", "description": "A language" },
+                { "title": "Book" }
+            ] }),
+        ),
+        (
+            "web_scraper",
+            json!({ "startUrls": ["https://example.com"] }),
+            json!({ "url": "https://example.com", "text": "Hello" }),
+        ),
+    ];
+
+    for (actor_type, actor_config, item) in cases {
+        let key_mapping = config(actor_type, actor_config).key_mapping(&[]);
+        let settings = ExtractionSettings::default();
+        validate_mappings(&key_mapping, None, &settings).unwrap();
+
+        let extraction = extract_export_items(
+            vec![item],
+            &key_mapping,
+            &settings,
+            &ExtractionContext::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            extraction.items.len(),
+            1,
+            "{}: {:?}",
+            actor_type,
+            extraction.report
+        );
+    }
+}
+
+#[test]
+fn test_default_key_mapping_content() {
+    let extract = |actor_type: &str, actor_config: Value, item: Value| {
+        let key_mapping = config(actor_type, actor_config).key_mapping(&[]);
+        extract_export_items(
+            vec![item],
+            &key_mapping,
+            &ExtractionSettings::default(),
+            &ExtractionContext::default(),
+        )
+        .unwrap()
+        .items
+        .remove(0)
+    };
+
+    let post = extract(
+        "instagram",
+        json!({ "usernames": ["rust"] }),
+        json!({ "id": "7", "caption": "New release", "timestamp": "2024-03-02T10:30:00.000Z", "ownerUsername": "rust" }),
+    );
+    assert_eq!(post.id.as_deref(), Some("7"));
+    assert_eq!(post.content, "New release");
+    assert_eq!(post.date.to_rfc3339(), "2024-03-02T00:00:00+00:00");
+    assert_eq!(post.metadata["author"], "rust");
+
+    let page = extract(
+        "google_search",
+        json!({ "queries": ["rust"] }),
+        json!({ "organicResults": [
+            { "title": "Rust", "description": "A language" },
+            { "title": "Book" }
+        ] }),
+    );
+    assert_eq!(page.content, "Rust\nA language\n\nBook\n");
+}
+
+#[test]
+fn test_key_mapping_overrides_defaults_by_target() {
+    let config = config(
+        "tripadvisor",
+        json!({ "url": "https://www.tripadvisor.com/Hotel_Review-x" }),
+    );
+    let overrides: Vec<KeyMapping> = serde_json::from_value(json!([
+        { "from": "title", "to": "content", "kind": "String" },
+        { "from": "rating", "to": "rating", "kind": "String" }
+    ]))
+    .unwrap();
+
+    let key_mapping = config.key_mapping(&overrides);
+
+    let targets: Vec<(&str, &str)> = key_mapping
+        .iter()
+        .map(|m| (m.from[0].as_str(), m.to.as_str()))
+        .collect();
+    assert_eq!(
+        targets,
+        vec![
+            ("id", "id"),
+            ("title", "content"),
+            ("publishedDate", "date"),
+            ("title", "title"),
+            ("url", "url"),
+            ("rating", "rating"),
+        ]
+    );
+}
+
+#[test]
+fn test_key_mapping_without_sources_removes_the_default() {
+    let config = config(
+        "tripadvisor",
+        json!({ "url": "https://www.tripadvisor.com/Hotel_Review-x" }),
+    );
+    let overrides: Vec<KeyMapping> = serde_json::from_value(json!([
+        { "from": [], "to": "url", "kind": "String" },
+        { "from": [], "to": "rating", "kind": "String" }
+    ]))
+    .unwrap();
+
+    let key_mapping = config.key_mapping(&overrides);
+
+    let targets: Vec<&str> = key_mapping.iter().map(|m| m.to.as_str()).collect();
+    assert_eq!(targets, vec!["id", "content", "date", "title", "rating"]);
+    // Removing a target without a default is still reported
+    let errors = validate_mappings(&key_mapping, None, &ExtractionSettings::default()).unwrap_err();
+    assert_eq!(fields(&errors), vec!["key_mapping[4].from"]);
+}
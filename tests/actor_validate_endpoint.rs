@@ -0,0 +1,171 @@
+use apify_connector::handlers::{get_remote_actor_schema, handle_actor_validate};
+use axum::{
+    http::StatusCode,
+    routing::{get, post},
+    Router,
+};
+use http_body_util::BodyExt;
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+fn app() -> Router {
+    Router::new()
+        .route("/actors/{actor_type}/validate", post(handle_actor_validate))
+        .route("/actors/remote/{*actor_id}", get(get_remote_actor_schema))
+}
+
+async fn post_validate(actor_type: &str, body: Value) -> (StatusCode, Value) {
+    let response = app()
+        .oneshot(
+            axum::http::Request::builder()
+                .method("POST")
+                .uri(format!("/actors/{}/validate", actor_type))
+                .header("content-type", "application/json")
+                .body(axum::body::Body::from(body.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let status = response.status();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    (status, serde_json::from_slice(&body).unwrap())
+}
+
+fn fields(body: &Value) -> Vec<&str> {
+    body["errors"]
+        .as_array()
+        .map(|errors| {
+            errors
+                .iter()
+                .map(|e| e["field"].as_str().unwrap())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[tokio::test]
+async fn test_valid_job_returns_the_apify_body() {
+    let (status, body) = post_validate(
+        "tripadvisor",
+        json!({
+            "actor_config": { "url": "https://www.tripadvisor.com/Hotel_Review-x" },
+            "state_mapping": [
+                { "from": "max", "to": "maxReviews", "update": "$max + 10" }
+            ],
+            "state": { "max": 20 }
+        }),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["valid"], true);
+    assert_eq!(
+        body["body"],
+        json!({
+            "startUrls": [{ "url": "https://www.tripadvisor.com/Hotel_Review-x", "method": "GET" }],
+            "reviewRatings": ["ALL_REVIEW_RATINGS"],
+            "reviewsLanguages": ["ALL_REVIEW_LANGUAGES"],
+            "maxReviews": 20
+        })
+    );
+    assert_eq!(body["key_mapping"][1]["to"], "content");
+    assert!(body.get("errors").is_none());
+    assert!(body.get("violations").is_none());
+}
+
+#[tokio::test]
+async fn test_every_problem_is_collected() {
+    let (status, body) = post_validate(
+        "google_search",
+        json!({
+            "actor_config": { "queries": "rust", "maxResults": "ten" },
+            "key_mapping": [
+                { "from": "date", "to": "date", "kind": "String" }
+            ],
+            "state_mapping": [
+                { "from": "", "to": "maxResults", "update": "$ +" }
+            ],
+            "filter": "item.rating >"
+        }),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["valid"], false);
+    assert!(body.get("body").is_none());
+
+    let mut pointers: Vec<&str> = body["violations"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|v| v["pointer"].as_str().unwrap())
+        .collect();
+    pointers.sort();
+    assert_eq!(pointers, vec!["/maxResults", "/queries"]);
+    assert_eq!(
+        fields(&body),
+        vec![
+            "key_mapping[1].kind",
+            "state_mapping[0].from",
+            "state_mapping[0].update",
+            "filter"
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_config_checks_and_state_injection_are_reported() {
+    let (status, body) = post_validate(
+        "google_search",
+        json!({ "actor_config": { "queries": [" "] } }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(fields(&body), vec!["actor_config"]);
+    assert_eq!(body["errors"][0]["message"], "query cannot be empty");
+
+    let (status, body) = post_validate(
+        "google_search",
+        json!({
+            "actor_config": { "queries": ["rust"] },
+            "state_mapping": [
+                { "from": "max", "to": "maxResults", "update": { "keep": true } },
+                { "from": "page", "to": "page", "update": { "keep": true } }
+            ],
+            "state": { "max": 500 }
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(fields(&body), vec!["state_mapping[1].to"]);
+    assert_eq!(
+        body["errors"][0]["message"],
+        "'page' is not a field of the google_search config"
+    );
+    assert!(body.get("body").is_none());
+
+    let (status, body) = post_validate(
+        "google_search",
+        json!({
+            "actor_config": { "queries": ["rust"] },
+            "state_mapping": [
+                { "from": "max", "to": "maxResults", "update": { "keep": true } }
+            ],
+            "state": { "max": 500 }
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(fields(&body), vec!["state_mapping"]);
+    assert!(body["errors"][0]["message"]
+        .as_str()
+        .unwrap()
+        .contains("max_results must be between 1 and 100"));
+}
+
+#[tokio::test]
+async fn test_unknown_actor_type_is_not_found() {
+    let (status, _) = post_validate("unknown", json!({ "actor_config": {} })).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
@@ -1,4 +1,9 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use apify_connector::actors::remote::{ActorCache, RemoteActor};
 use apify_connector::actors::{get_actor_metadata, list_available_actors, ActorMetadata};
+use apify_connector::dto::{ActorDetails, ApiResponse};
 use axum::{extract::Path, http::StatusCode, routing::get, Json, Router};
 use http_body_util::BodyExt;
 use serde_json::Value;
@@ -261,6 +266,12 @@ async fn test_get_single_actor_schema() {
     let props = &actor["schema"]["properties"];
     assert!(props.get("url").is_some(), "missing url");
     assert!(props.get("reviewRatings").is_some(), "missing reviewRatings");
+
+    let key_mapping = &actor["default_key_mapping"];
+    assert_eq!(key_mapping[1]["from"], serde_json::json!(["text"]));
+    assert_eq!(key_mapping[1]["to"], "content");
+    assert_eq!(key_mapping[2]["from"], serde_json::json!(["publishedDate"]));
+    assert_eq!(key_mapping[2]["kind"]["Date"]["format"], "%Y-%m-%d");
 }
 
 #[tokio::test]
@@ -318,3 +329,119 @@ async fn test_get_all_actor_types_individually() {
         );
     }
 }
+
+#[test]
+fn test_remote_actor_details_are_parsed() {
+    let response: ApiResponse<ActorDetails> = serde_json::from_value(serde_json::json!({
+        "data": {
+            "id": "moJRLRc85AitArpNN",
+            "name": "web-scraper",
+            "username": "apify",
+            "title": "Web Scraper",
+            "taggedBuilds": { "latest": { "buildId": "z2EryhbfhgSyqj6Hn", "buildNumber": "0.3.4" } },
+            "stats": { "totalRuns": 1 }
+        }
+    }))
+    .unwrap();
+
+    let actor = response.data;
+    assert_eq!(actor.username, "apify");
+    assert_eq!(actor.description, None);
+    assert_eq!(
+        actor.tagged_builds["latest"].build_id.as_deref(),
+        Some("z2EryhbfhgSyqj6Hn")
+    );
+}
+
+#[test]
+fn test_remote_actor_metadata_has_the_typed_actor_shape() {
+    let actor = RemoteActor {
+        id: "moJRLRc85AitArpNN".into(),
+        name: "apify/web-scraper".into(),
+        title: Some("Web Scraper".into()),
+        description: None,
+        input_schema: Some(serde_json::json!({
+            "title": "Web Scraper input",
+            "type": "object",
+            "schemaVersion": 1,
+            "properties": {
+                "startUrls": { "title": "Start URLs", "type": "array", "editor": "requestListSources" }
+            },
+            "required": ["startUrls"]
+        })),
+    };
+
+    let metadata = serde_json::to_value(actor.metadata().unwrap()).unwrap();
+
+    assert_eq!(metadata["actor_type"], "moJRLRc85AitArpNN");
+    assert_eq!(metadata["actor_name"], "apify/web-scraper");
+    assert_eq!(metadata["schema"]["required"], serde_json::json!(["startUrls"]));
+    assert_eq!(
+        metadata["schema"]["properties"]["startUrls"]["editor"],
+        "requestListSources"
+    );
+    assert_eq!(metadata["schema"]["schemaVersion"], 1);
+    assert!(metadata.get("default_key_mapping").is_none());
+
+    let without_schema = RemoteActor {
+        input_schema: None,
+        ..actor
+    };
+    assert_eq!(
+        serde_json::to_value(without_schema.metadata().unwrap().schema).unwrap(),
+        serde_json::json!({})
+    );
+}
+
+fn remote_actor(name: &str) -> Arc<RemoteActor> {
+    Arc::new(RemoteActor {
+        id: name.into(),
+        name: name.into(),
+        title: None,
+        description: None,
+        input_schema: None,
+    })
+}
+
+#[test]
+fn test_remote_actor_cache_serves_fresh_entries_per_token() {
+    let mut cache = ActorCache::new(Duration::from_secs(600), 8);
+    cache.insert("apify/web-scraper", None, remote_actor("public"));
+    cache.insert("apify/web-scraper", Some("token"), remote_actor("private"));
+
+    assert_eq!(cache.get("apify/web-scraper", None).unwrap().name, "public");
+    assert_eq!(
+        cache.get("apify/web-scraper", Some("token")).unwrap().name,
+        "private"
+    );
+    assert!(cache.get("apify/web-scraper", Some("other")).is_none());
+    assert!(cache.get("apify/other", None).is_none());
+}
+
+#[test]
+fn test_remote_actor_cache_entries_expire() {
+    let mut cache = ActorCache::new(Duration::ZERO, 8);
+    cache.insert("apify/web-scraper", None, remote_actor("a"));
+
+    assert!(cache.get("apify/web-scraper", None).is_none());
+}
+
+#[test]
+fn test_full_remote_actor_cache_drops_expired_entries_first() {
+    let mut cache = ActorCache::new(Duration::from_millis(200), 2);
+    cache.insert("a", None, remote_actor("a"));
+    std::thread::sleep(Duration::from_millis(250));
+    cache.insert("b", None, remote_actor("b"));
+
+    // Full: only the expired entry is dropped
+    cache.insert("c", None, remote_actor("c"));
+    assert_eq!(cache.len(), 2);
+    assert!(cache.get("b", None).is_some());
+    assert!(cache.get("c", None).is_some());
+
+    // Full with fresh entries only: everything is dropped
+    cache.insert("d", None, remote_actor("d"));
+    assert_eq!(cache.len(), 1);
+    assert!(cache.get("b", None).is_none());
+    assert!(cache.get("d", None).is_some());
+}
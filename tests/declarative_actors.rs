@@ -0,0 +1,197 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use apify_connector::actors::declarative::{self, load_dir};
+use apify_connector::actors::{
+    get_actor_metadata, list_available_actors, ActorConfig, ConnectorActor, DeclarativeActor,
+};
+use serde_json::json;
+
+const REVIEWS_ACTOR: &str = r#"
+actor_type: booking_reviews
+actor_name: voyager/booking-reviews-scraper
+input_schema:
+  type: object
+  required: [hotelUrl]
+  properties:
+    hotelUrl: { type: string, pattern: "^https://" }
+    maxReviews: { type: integer, minimum: 1 }
+    language: { type: string }
+defaults:
+  maxReviews: 50
+body_template:
+  startUrls:
+    - url: "{hotelUrl}"
+  maxReviewsPerHotel: "{maxReviews}"
+  reviewsLanguage: "{language}"
+  label: "reviews of {hotelUrl}"
+key_mapping:
+  - { from: reviewText, to: content, kind: String }
+  - { from: reviewDate, to: date, kind: { Date: { format: "%Y-%m-%d" } } }
+"#;
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("apify-connector-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn write(dir: &Path, file: &str, contents: &str) -> PathBuf {
+    let path = dir.join(file);
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+fn reviews_actor(name: &str) -> Arc<DeclarativeActor> {
+    let dir = temp_dir(name);
+    let path = write(&dir, "booking.yaml", REVIEWS_ACTOR);
+    Arc::new(DeclarativeActor::load(&path).unwrap())
+}
+
+#[test]
+fn test_body_is_built_from_template_and_defaults() {
+    let actor = reviews_actor("declarative-body");
+
+    let config = actor
+        .parse(json!({ "hotelUrl": "https://booking.com/hotel/x" }))
+        .unwrap();
+
+    assert_eq!(
+        serde_json::to_value(actor.to_body(&config).unwrap()).unwrap(),
+        json!({
+            "startUrls": [{ "url": "https://booking.com/hotel/x" }],
+            "maxReviewsPerHotel": 50,
+            "label": "reviews of https://booking.com/hotel/x"
+        })
+    );
+}
+
+#[test]
+fn test_config_is_checked_against_the_input_schema() {
+    let actor = reviews_actor("declarative-schema");
+
+    let error = actor
+        .parse(json!({ "hotelUrl": "http://booking.com", "maxReviews": 0 }))
+        .unwrap_err();
+
+    let pointers: Vec<&str> = error.violations.iter().map(|v| v.pointer.as_str()).collect();
+    assert_eq!(pointers, vec!["/hotelUrl", "/maxReviews"], "{}", error);
+    assert!(
+        actor
+            .parse(json!({}))
+            .unwrap_err()
+            .to_string()
+            .contains("hotelUrl")
+    );
+    assert!(actor.parse(json!([])).is_err());
+}
+
+#[test]
+fn test_metadata_exposes_schema_and_key_mapping() {
+    let metadata = serde_json::to_value(reviews_actor("declarative-metadata").metadata()).unwrap();
+
+    assert_eq!(metadata["actor_type"], "booking_reviews");
+    assert_eq!(metadata["actor_name"], "voyager/booking-reviews-scraper");
+    assert!(metadata["schema"]["properties"]["hotelUrl"].is_object());
+    assert_eq!(metadata["default_key_mapping"][0]["to"], "content");
+    assert_eq!(
+        metadata["default_key_mapping"][1]["from"],
+        json!(["reviewDate"])
+    );
+}
+
+#[test]
+fn test_load_dir_skips_invalid_definitions() {
+    let dir = temp_dir("declarative-invalid");
+    write(&dir, "a_booking.yaml", REVIEWS_ACTOR);
+    write(
+        &dir,
+        "b_duplicate.yml",
+        &REVIEWS_ACTOR.replace("voyager/booking-reviews-scraper", "other/actor"),
+    );
+    write(
+        &dir,
+        "c_typed.json",
+        r#"{ "actor_type": "web_scraper", "actor_name": "x/y", "input_schema": {} }"#,
+    );
+    write(
+        &dir,
+        "d_bad_schema.json",
+        r#"{ "actor_type": "bad", "actor_name": "x/y", "input_schema": { "type": 12 } }"#,
+    );
+    write(
+        &dir,
+        "e_unknown_field.yaml",
+        "actor_type: x\nactor_name: y\ninput_schema: {}\nbody: {}\n",
+    );
+    write(&dir, "notes.txt", "not an actor");
+
+    let (actors, errors) = load_dir(&dir).unwrap();
+
+    let types: Vec<&str> = actors
+        .iter()
+        .map(|a| a.definition.actor_type.as_str())
+        .collect();
+    assert_eq!(types, vec!["booking_reviews"]);
+    assert_eq!(errors.len(), 4, "{:?}", errors);
+    assert!(errors[0].contains("b_duplicate.yml") && errors[0].contains("already defined"));
+    assert!(errors[1].contains("c_typed.json") && errors[1].contains("already defined"));
+    assert!(errors[2].contains("d_bad_schema.json") && errors[2].contains("Invalid input_schema"));
+    assert!(errors[3].contains("e_unknown_field.yaml"));
+}
+
+#[test]
+fn test_registered_actors_are_served_and_reloaded() {
+    let dir = temp_dir("declarative-registry");
+    let path = write(&dir, "booking.yaml", REVIEWS_ACTOR);
+
+    assert_eq!(declarative::reload(&dir).unwrap(), 1);
+
+    let types: Vec<String> = list_available_actors()
+        .into_iter()
+        .map(|a| a.actor_type)
+        .collect();
+    assert_eq!(types.len(), 5);
+    assert_eq!(types[4], "booking_reviews");
+    assert!(get_actor_metadata("booking_reviews").is_some());
+
+    let config = ActorConfig::from_type_and_config(
+        "booking_reviews",
+        json!({ "hotelUrl": "https://booking.com/hotel/x", "language": "fr" }),
+    )
+    .unwrap();
+    assert_eq!(config.actor_name(), "voyager/booking-reviews-scraper");
+    assert_eq!(config.to_body().unwrap()["reviewsLanguage"], "fr");
+
+    let error = ActorConfig::from_type_and_config("booking_reviews", json!({})).unwrap_err();
+    assert!(error.to_string().starts_with("Invalid booking_reviews config"));
+
+    std::fs::write(
+        &path,
+        REVIEWS_ACTOR.replace("maxReviews: 50", "maxReviews: 10"),
+    )
+    .unwrap();
+    declarative::reload(&dir).unwrap();
+    let config = ActorConfig::from_type_and_config(
+        "booking_reviews",
+        json!({ "hotelUrl": "https://booking.com/hotel/x" }),
+    )
+    .unwrap();
+    assert_eq!(config.to_body().unwrap()["maxReviewsPerHotel"], json!(10));
+
+    // A half-written file keeps the last loaded definition
+    std::fs::write(&path, "actor_type: booking_reviews\nactor_name: [").unwrap();
+    assert_eq!(declarative::reload(&dir).unwrap(), 1);
+    let config = ActorConfig::from_type_and_config(
+        "booking_reviews",
+        json!({ "hotelUrl": "https://booking.com/hotel/x" }),
+    )
+    .unwrap();
+    assert_eq!(config.to_body().unwrap()["maxReviewsPerHotel"], json!(10));
+
+    std::fs::remove_file(&path).unwrap();
+    declarative::reload(&dir).unwrap();
+    assert!(get_actor_metadata("booking_reviews").is_none());
+    assert_eq!(list_available_actors().len(), 4);
+}
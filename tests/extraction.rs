@@ -0,0 +1,753 @@
+use apify_connector::dto::{ExtractionSettings, KeyMapping};
+use apify_connector::extraction::{
+    extract_export_items, Extraction, ExtractionContext, MAX_ERROR_SAMPLES, SEEN_DIGESTS_KEY,
+};
+use serde_json::{json, Value};
+
+fn mappings(value: Value) -> Vec<KeyMapping> {
+    serde_json::from_value(value).unwrap()
+}
+
+fn settings(value: Value) -> ExtractionSettings {
+    serde_json::from_value(value).unwrap()
+}
+
+fn extract(
+    data: Vec<Value>,
+    key_mapping: &[KeyMapping],
+    settings: &ExtractionSettings,
+) -> anyhow::Result<Extraction> {
+    extract_export_items(data, key_mapping, settings, &ExtractionContext::default())
+}
+
+fn review_mappings() -> Vec<KeyMapping> {
+    mappings(json!([
+        { "from": "text", "to": "content", "kind": "String" },
+        { "from": "publishedDate", "to": "date", "kind": { "Date": { "format": "%Y-%m-%d" } } },
+        { "from": "id", "to": "id", "kind": "String" }
+    ]))
+}
+
+#[test]
+fn test_report_counts_kept_and_dropped_items() {
+    let data = vec![
+        json!({ "id": "1", "text": "great", "publishedDate": "2024-01-15" }),
+        json!({ "id": "2", "publishedDate": "2024-01-16" }),
+        json!({ "id": "3", "text": "meh" }),
+        json!({ "id": "4", "text": "bad", "publishedDate": "16/01/2024" }),
+    ];
+
+    let extraction = extract(data, &review_mappings(), &ExtractionSettings::default()).unwrap();
+
+    assert_eq!(extraction.items.len(), 1);
+    assert_eq!(extraction.report.seen, 4);
+    assert_eq!(extraction.report.kept, 1);
+    assert_eq!(extraction.report.dropped, 3);
+
+    let indexes: Vec<usize> = extraction.report.errors.iter().map(|e| e.index).collect();
+    assert_eq!(indexes, vec![1, 2, 3]);
+    assert!(extraction.report.errors[0].reason.contains("content"));
+    assert!(extraction.report.errors[1].reason.contains("date"));
+    assert!(extraction.report.errors[2].reason.contains("16/01/2024"));
+}
+
+#[test]
+fn test_error_samples_are_capped() {
+    let data = (0..MAX_ERROR_SAMPLES + 5)
+        .map(|i| json!({ "id": i.to_string() }))
+        .collect();
+
+    let extraction = extract(data, &review_mappings(), &ExtractionSettings::default()).unwrap();
+
+    assert_eq!(extraction.report.dropped, MAX_ERROR_SAMPLES + 5);
+    assert_eq!(extraction.report.errors.len(), MAX_ERROR_SAMPLES);
+}
+
+#[test]
+fn test_strict_mode_fails_above_threshold() {
+    let data = vec![
+        json!({ "text": "ok", "publishedDate": "2024-01-15" }),
+        json!({ "text": "ko", "publishedDate": "Jan 15, 2024" }),
+    ];
+
+    let err = extract(
+        data,
+        &review_mappings(),
+        &settings(json!({ "strict": { "max_drop_ratio": 0.25 } })),
+    )
+    .unwrap_err();
+
+    assert!(err.to_string().contains("Dropped 1 of 2 items"));
+}
+
+#[test]
+fn test_strict_mode_passes_within_threshold() {
+    let data = vec![
+        json!({ "text": "ok", "publishedDate": "2024-01-15" }),
+        json!({ "text": "ko", "publishedDate": "Jan 15, 2024" }),
+    ];
+
+    let extraction = extract(
+        data,
+        &review_mappings(),
+        &settings(json!({ "strict": { "max_drop_ratio": 0.5 } })),
+    )
+    .unwrap();
+
+    assert_eq!(extraction.report.kept, 1);
+}
+
+#[test]
+fn test_strict_mode_ignores_filtered_items() {
+    // 18 items filtered out, 2 kept by the filter of which 1 cannot be mapped
+    let mut data: Vec<Value> = (0..18)
+        .map(|_| json!({ "text": "skip", "rating": 5, "publishedDate": "2024-01-15" }))
+        .collect();
+    data.push(json!({ "text": "ok", "rating": 1, "publishedDate": "2024-01-15" }));
+    data.push(json!({ "text": "ko", "rating": 1, "publishedDate": "Jan 15, 2024" }));
+
+    let err = extract(
+        data,
+        &review_mappings(),
+        &settings(json!({ "filter": "item.rating < 3", "strict": { "max_drop_ratio": 0.25 } })),
+    )
+    .unwrap_err();
+
+    assert!(err.to_string().contains("Dropped 1 of 2 items (50.0%)"), "{}", err);
+}
+
+#[test]
+fn test_strict_mode_accepts_everything_filtered() {
+    let data = vec![json!({ "text": "skip", "rating": 5, "publishedDate": "2024-01-15" })];
+
+    let extraction = extract(
+        data,
+        &review_mappings(),
+        &settings(json!({ "filter": "item.rating < 3", "strict": {} })),
+    )
+    .unwrap();
+
+    assert_eq!(extraction.report.filtered, 1);
+}
+
+#[test]
+fn test_strict_mode_accepts_empty_dataset() {
+    let extraction = extract(
+        vec![],
+        &review_mappings(),
+        &settings(json!({ "strict": {} })),
+    )
+    .unwrap();
+
+    assert_eq!(extraction.report.seen, 0);
+}
+
+#[test]
+fn test_first_non_null_candidate_wins() {
+    let key_mapping = mappings(json!([
+        { "from": ["text", "caption", "reviewBody"], "to": "content", "kind": "String" },
+        { "from": "date", "to": "date", "kind": { "Date": { "format": "%Y-%m-%d" } } }
+    ]));
+    let data = vec![
+        json!({ "text": "from text", "caption": "from caption", "date": "2024-01-15" }),
+        json!({ "text": null, "caption": "from caption", "date": "2024-01-15" }),
+        json!({ "reviewBody": "from body", "date": "2024-01-15" }),
+    ];
+
+    let extraction = extract(data, &key_mapping, &ExtractionSettings::default()).unwrap();
+
+    let contents: Vec<&str> = extraction
+        .items
+        .iter()
+        .map(|i| i.content.as_str())
+        .collect();
+    assert_eq!(contents, vec!["from text", "from caption", "from body"]);
+    // Candidate fields never leak into metadata
+    assert!(!extraction.items[0].metadata.contains_key("caption"));
+}
+
+#[test]
+fn test_defaults_fill_missing_fields() {
+    let key_mapping = mappings(json!([
+        { "from": "text", "to": "content", "kind": "String", "default": "" },
+        { "from": "date", "to": "date", "kind": { "Date": { "format": "%Y-%m-%d" } }, "default": "now" },
+        { "from": "lang", "to": "language", "kind": "String", "default": "en" }
+    ]));
+    let data = vec![json!({ "url": "https://example.com" })];
+
+    let before = chrono::Utc::now() - chrono::Duration::seconds(1);
+    let extraction = extract(data, &key_mapping, &ExtractionSettings::default()).unwrap();
+
+    assert_eq!(extraction.report.dropped, 0);
+    let item = &extraction.items[0];
+    assert_eq!(item.content, "");
+    assert!(item.date >= before);
+    assert_eq!(item.metadata["language"], "en");
+}
+
+#[test]
+fn test_date_default_uses_mapping_format() {
+    let key_mapping = mappings(json!([
+        { "from": "text", "to": "content", "kind": "String" },
+        { "from": "date", "to": "date", "kind": { "Date": { "format": "%d/%m/%Y" } }, "default": "01/02/2024" }
+    ]));
+    let data = vec![json!({ "text": "hello" })];
+
+    let extraction = extract(data, &key_mapping, &ExtractionSettings::default()).unwrap();
+
+    assert_eq!(
+        extraction.items[0].date.format("%Y-%m-%d").to_string(),
+        "2024-02-01"
+    );
+}
+
+fn with_date(mut key_mapping: Value) -> Vec<KeyMapping> {
+    key_mapping.as_array_mut().unwrap().push(
+        json!({ "from": "date", "to": "date", "kind": { "Date": { "format": "%Y-%m-%d" } } }),
+    );
+    mappings(key_mapping)
+}
+
+#[test]
+fn test_transform_sees_value_and_item() {
+    let key_mapping = with_date(json!([
+        { "from": "text", "to": "content", "kind": "String", "transform": "item.title + \"\\n\" + value" },
+        { "from": "author", "to": "author", "kind": "String", "transform": "let v = value; v.trim(); v.to_lower()" },
+        { "from": "url", "to": "id", "kind": "String", "transform": "regex_extract(value, \"/review/(\\\\d+)\")" }
+    ]));
+    let data = vec![json!({
+        "title": "Great",
+        "text": "Loved it",
+        "author": "  JANE  ",
+        "url": "https://example.com/review/42",
+        "date": "2024-01-15"
+    })];
+
+    let extraction = extract(data, &key_mapping, &ExtractionSettings::default()).unwrap();
+
+    let item = &extraction.items[0];
+    assert_eq!(item.content, "Great\nLoved it");
+    assert_eq!(item.metadata["author"], "jane");
+    assert_eq!(item.id.as_deref(), Some("42"));
+}
+
+#[test]
+fn test_transform_unit_result_falls_back_to_default() {
+    let key_mapping = with_date(json!([
+        { "from": "text", "to": "content", "kind": "String" },
+        { "from": "url", "to": "id", "kind": "String", "transform": "regex_extract(value, \"/review/(\\\\d+)\")", "default": "unknown" }
+    ]));
+    let data =
+        vec![json!({ "text": "hi", "url": "https://example.com/about", "date": "2024-01-15" })];
+
+    let extraction = extract(data, &key_mapping, &ExtractionSettings::default()).unwrap();
+
+    assert_eq!(extraction.items[0].id.as_deref(), Some("unknown"));
+}
+
+#[test]
+fn test_transform_runtime_error_drops_item() {
+    let key_mapping = with_date(json!([
+        { "from": "text", "to": "content", "kind": "String", "transform": "value.no_such_function()" }
+    ]));
+    let data = vec![json!({ "text": "hi", "date": "2024-01-15" })];
+
+    let extraction = extract(data, &key_mapping, &ExtractionSettings::default()).unwrap();
+
+    assert_eq!(extraction.report.dropped, 1);
+    assert!(extraction.report.errors[0]
+        .reason
+        .contains("Transform for 'content' failed"));
+}
+
+#[test]
+fn test_transform_syntax_error_fails_extraction() {
+    let key_mapping = with_date(json!([
+        { "from": "text", "to": "content", "kind": "String", "transform": "value +" }
+    ]));
+
+    let err = extract(vec![], &key_mapping, &ExtractionSettings::default()).unwrap_err();
+
+    assert!(err.to_string().contains("Invalid transform for 'content'"));
+}
+
+#[test]
+fn test_filter_skips_items_and_reports_them() {
+    let data = vec![
+        json!({ "text": "awful", "rating": 1, "publishedDate": "2024-01-15" }),
+        json!({ "text": "great", "rating": 5, "publishedDate": "2024-01-15" }),
+        json!({ "text": "meh", "rating": 2, "publishedDate": "2024-01-16" }),
+    ];
+
+    let extraction = extract(
+        data,
+        &review_mappings(),
+        &settings(json!({ "filter": "item.rating <= 2" })),
+    )
+    .unwrap();
+
+    let contents: Vec<&str> = extraction
+        .items
+        .iter()
+        .map(|i| i.content.as_str())
+        .collect();
+    assert_eq!(contents, vec!["awful", "meh"]);
+    assert_eq!(extraction.report.seen, 3);
+    assert_eq!(extraction.report.filtered, 1);
+    assert_eq!(extraction.report.dropped, 0);
+}
+
+#[test]
+fn test_filter_can_read_state() {
+    let data = vec![
+        json!({ "text": "old", "publishedDate": "2024-01-10" }),
+        json!({ "text": "new", "publishedDate": "2024-01-20" }),
+    ];
+    let ctx = ExtractionContext {
+        state: serde_json::from_value(json!({ "last_date": "2024-01-15" })).unwrap(),
+        ..Default::default()
+    };
+
+    let extraction = extract_export_items(
+        data,
+        &review_mappings(),
+        &settings(json!({ "filter": "item.publishedDate > state.last_date" })),
+        &ctx,
+    )
+    .unwrap();
+
+    assert_eq!(extraction.items.len(), 1);
+    assert_eq!(extraction.items[0].content, "new");
+}
+
+#[test]
+fn test_filter_returning_non_bool_drops_item() {
+    let data = vec![json!({ "text": "x", "publishedDate": "2024-01-10" })];
+
+    let extraction = extract(
+        data,
+        &review_mappings(),
+        &settings(json!({ "filter": "item.text" })),
+    )
+    .unwrap();
+
+    assert_eq!(extraction.report.dropped, 1);
+    assert!(extraction.report.errors[0]
+        .reason
+        .contains("must return a bool"));
+}
+
+fn page_mappings() -> Vec<KeyMapping> {
+    mappings(json!([
+        { "from": "text", "to": "content", "kind": "String" },
+        { "from": "url", "to": "id", "kind": "String" },
+        { "from": "date", "to": "date", "kind": { "Date": { "format": "%Y-%m-%d" } }, "default": "now" }
+    ]))
+}
+
+#[test]
+fn test_dedupe_by_id_within_run() {
+    let data = vec![
+        json!({ "url": "https://a", "text": "first" }),
+        json!({ "url": "https://b", "text": "second" }),
+        json!({ "url": "https://a", "text": "first again" }),
+        json!({ "text": "no id" }),
+        json!({ "text": "no id" }),
+    ];
+
+    let extraction = extract(
+        data,
+        &page_mappings(),
+        &settings(json!({ "dedupe": { "by": "Id" } })),
+    )
+    .unwrap();
+
+    let contents: Vec<&str> = extraction
+        .items
+        .iter()
+        .map(|i| i.content.as_str())
+        .collect();
+    assert_eq!(contents, vec!["first", "second", "no id", "no id"]);
+    assert_eq!(extraction.report.duplicates, 1);
+    assert!(extraction.seen_digests.is_none());
+}
+
+#[test]
+fn test_dedupe_by_fields_and_content() {
+    let data = vec![
+        json!({ "url": "https://a", "date": "2024-01-01", "text": "same" }),
+        json!({ "url": "https://a", "date": "2024-01-02", "text": "same" }),
+        json!({ "url": "https://a", "date": "2024-01-01", "text": "other" }),
+    ];
+
+    let by_fields = extract(
+        data.clone(),
+        &page_mappings(),
+        &settings(json!({ "dedupe": { "by": { "Fields": ["url", "date"] } } })),
+    )
+    .unwrap();
+    assert_eq!(by_fields.items.len(), 2);
+    assert_eq!(by_fields.report.duplicates, 1);
+
+    let by_content = extract(
+        data,
+        &page_mappings(),
+        &settings(json!({ "dedupe": { "by": "Content" } })),
+    )
+    .unwrap();
+    assert_eq!(by_content.items.len(), 2);
+    assert_eq!(by_content.items[1].content, "other");
+}
+
+#[test]
+fn test_dedupe_across_runs_uses_state() {
+    let dedupe = settings(json!({ "dedupe": { "by": "Id", "across_runs": true } }));
+
+    let first = extract(
+        vec![json!({ "url": "https://a", "text": "a" })],
+        &page_mappings(),
+        &dedupe,
+    )
+    .unwrap();
+    let seen = first.seen_digests.expect("digests should be returned");
+    assert_eq!(seen.len(), 1);
+
+    let ctx = ExtractionContext {
+        state: [(SEEN_DIGESTS_KEY.to_string(), json!(seen))].into(),
+        ..Default::default()
+    };
+    let second = extract_export_items(
+        vec![
+            json!({ "url": "https://a", "text": "a" }),
+            json!({ "url": "https://b", "text": "b" }),
+        ],
+        &page_mappings(),
+        &dedupe,
+        &ctx,
+    )
+    .unwrap();
+
+    assert_eq!(second.items.len(), 1);
+    assert_eq!(second.items[0].content, "b");
+    assert_eq!(second.report.duplicates, 1);
+    assert_eq!(second.seen_digests.unwrap().len(), 2);
+}
+
+#[test]
+fn test_dedupe_history_is_bounded() {
+    let data = (0..5)
+        .map(|i| json!({ "url": format!("https://{}", i), "text": "x" }))
+        .collect();
+
+    let extraction = extract(
+        data,
+        &page_mappings(),
+        &settings(json!({ "dedupe": { "by": "Id", "across_runs": true, "max_seen": 3 } })),
+    )
+    .unwrap();
+
+    assert_eq!(extraction.items.len(), 5);
+    assert_eq!(extraction.seen_digests.unwrap().len(), 3);
+}
+
+fn unidentified_mappings() -> Vec<KeyMapping> {
+    mappings(json!([
+        { "from": "text", "to": "content", "kind": "String" },
+        { "from": "date", "to": "date", "kind": { "Date": { "format": "%Y-%m-%d" } } }
+    ]))
+}
+
+fn pages() -> Vec<Value> {
+    vec![
+        json!({ "url": "https://a", "date": "2024-01-01", "text": "a" }),
+        json!({ "url": "https://b", "date": "2024-01-01", "text": "b" }),
+    ]
+}
+
+fn ids(extraction: &Extraction) -> Vec<String> {
+    extraction
+        .items
+        .iter()
+        .map(|i| i.id.clone().unwrap())
+        .collect()
+}
+
+#[test]
+fn test_hash_id_strategy_is_stable() {
+    let id_strategy = settings(json!({ "id_strategy": { "Hash": { "fields": ["url", "date"] } } }));
+
+    let first = extract(pages(), &unidentified_mappings(), &id_strategy).unwrap();
+    let second = extract(pages(), &unidentified_mappings(), &id_strategy).unwrap();
+
+    let first_ids = ids(&first);
+    assert_eq!(first_ids, ids(&second));
+    assert_ne!(first_ids[0], first_ids[1]);
+    assert_eq!(first_ids[0].len(), 64);
+}
+
+#[test]
+fn test_hash_id_strategy_tells_missing_fields_apart() {
+    let id_strategy = settings(json!({ "id_strategy": { "Hash": { "fields": ["url", "title"] } } }));
+    let items = vec![
+        json!({ "url": "https://a", "date": "2024-01-01", "text": "a" }),
+        json!({ "title": "https://a", "date": "2024-01-01", "text": "b" }),
+        json!({ "url": "https://a", "title": "", "date": "2024-01-01", "text": "c" }),
+    ];
+
+    let extraction = extract(items, &unidentified_mappings(), &id_strategy).unwrap();
+
+    let ids = ids(&extraction);
+    assert_eq!(ids.len(), 3);
+    assert_ne!(ids[0], ids[1]);
+    assert_ne!(ids[0], ids[2]);
+}
+
+#[test]
+fn test_uuid_v5_id_strategy() {
+    let extraction = extract(
+        pages(),
+        &unidentified_mappings(),
+        &settings(json!({ "id_strategy": { "UuidV5": { "fields": ["url"] } } })),
+    )
+    .unwrap();
+
+    // UUIDv5 of "https://a" in the URL namespace
+    assert_eq!(ids(&extraction)[0], "f2c428cc-32d2-5108-b24b-233a0a976fcd");
+}
+
+#[test]
+fn test_uuid_v5_rejects_invalid_namespace() {
+    let err = extract(
+        pages(),
+        &unidentified_mappings(),
+        &settings(
+            json!({ "id_strategy": { "UuidV5": { "namespace": "nope", "fields": ["url"] } } }),
+        ),
+    )
+    .unwrap_err();
+
+    assert!(err.to_string().contains("Invalid UUID namespace"));
+}
+
+#[test]
+fn test_run_index_id_strategy() {
+    let ctx = ExtractionContext {
+        run_id: Some("run42".into()),
+        ..Default::default()
+    };
+
+    let extraction = extract_export_items(
+        pages(),
+        &unidentified_mappings(),
+        &settings(json!({ "id_strategy": "RunIndex" })),
+        &ctx,
+    )
+    .unwrap();
+
+    assert_eq!(ids(&extraction), vec!["run42-0", "run42-1"]);
+}
+
+#[test]
+fn test_mapped_id_takes_precedence_over_strategy() {
+    let extraction = extract(
+        pages(),
+        &page_mappings(),
+        &settings(json!({ "id_strategy": { "Hash": { "fields": ["url"] } } })),
+    )
+    .unwrap();
+
+    assert_eq!(ids(&extraction), vec!["https://a", "https://b"]);
+}
+
+fn raw_page() -> Vec<Value> {
+    vec![json!({
+        "text": "content",
+        "date": "2024-01-01",
+        "title": "Title",
+        "html": "<html>...</html>",
+        "metaDescription": "desc",
+        "metaKeywords": "kw",
+        "#debug": "internal",
+        "count": 3
+    })]
+}
+
+fn metadata_keys(extraction: &Extraction) -> Vec<String> {
+    let mut keys: Vec<String> = extraction.items[0].metadata.keys().cloned().collect();
+    keys.sort();
+    keys
+}
+
+#[test]
+fn test_passthrough_defaults_to_all_string_fields() {
+    let extraction = extract(
+        raw_page(),
+        &unidentified_mappings(),
+        &ExtractionSettings::default(),
+    )
+    .unwrap();
+
+    assert_eq!(
+        metadata_keys(&extraction),
+        vec!["#debug", "html", "metaDescription", "metaKeywords", "title"]
+    );
+}
+
+#[test]
+fn test_passthrough_none_keeps_only_mapped_fields() {
+    let key_mapping = mappings(json!([
+        { "from": "text", "to": "content", "kind": "String" },
+        { "from": "date", "to": "date", "kind": { "Date": { "format": "%Y-%m-%d" } } },
+        { "from": "title", "to": "headline", "kind": "String" }
+    ]));
+
+    let extraction = extract(
+        raw_page(),
+        &key_mapping,
+        &settings(json!({ "passthrough": { "mode": "None" } })),
+    )
+    .unwrap();
+
+    assert_eq!(metadata_keys(&extraction), vec!["headline"]);
+}
+
+#[test]
+fn test_passthrough_allowlist_and_denylist() {
+    let allowed = extract(
+        raw_page(),
+        &unidentified_mappings(),
+        &settings(json!({ "passthrough": { "mode": { "Allow": ["meta*", "titl?"] } } })),
+    )
+    .unwrap();
+    assert_eq!(
+        metadata_keys(&allowed),
+        vec!["metaDescription", "metaKeywords", "title"]
+    );
+
+    let denied = extract(
+        raw_page(),
+        &unidentified_mappings(),
+        &settings(json!({ "passthrough": { "mode": { "Deny": ["html", "#*", "*Keywords"] } } })),
+    )
+    .unwrap();
+    assert_eq!(metadata_keys(&denied), vec!["metaDescription", "title"]);
+}
+
+#[test]
+fn test_passthrough_truncates_long_values() {
+    let extraction = extract(
+        vec![json!({ "text": "content", "date": "2024-01-01", "html": "héllo wörld" })],
+        &unidentified_mappings(),
+        &settings(json!({ "passthrough": { "max_length": 5 } })),
+    )
+    .unwrap();
+
+    assert_eq!(extraction.items[0].metadata["html"], "héllo");
+    // Mapped values are never truncated
+    assert_eq!(extraction.items[0].content, "content");
+}
+
+#[test]
+fn test_html_kinds_clean_mapped_values() {
+    let key_mapping = mappings(json!([
+        { "from": "body", "to": "content", "kind": "HtmlParagraphs" },
+        { "from": "title", "to": "title", "kind": "HtmlText" },
+        { "from": "date", "to": "date", "kind": { "Date": { "format": "%Y-%m-%d" } } }
+    ]));
+    let data = vec![json!({
+        "body": "<p>Loved the <b>food</b>.</p><p>Service &amp; staff were great.</p>",
+        "title": "<h2>Best   &quot;pizza&quot;</h2>",
+        "date": "2024-01-01"
+    })];
+
+    let extraction = extract(data, &key_mapping, &ExtractionSettings::default()).unwrap();
+
+    let item = &extraction.items[0];
+    assert_eq!(
+        item.content,
+        "Loved the food.\n\nService & staff were great."
+    );
+    assert_eq!(item.metadata["title"], "Best \"pizza\"");
+}
+
+#[test]
+fn test_content_template_combines_fields() {
+    let data = vec![
+        json!({ "title": "Great", "text": "Loved it", "date": "2024-01-01", "lang": "en" }),
+        json!({ "text": "No title", "date": "2024-01-01" }),
+    ];
+
+    let extraction = extract(
+        data,
+        &unidentified_mappings(),
+        &settings(json!({ "content_template": "{title}\n\n{text}" })),
+    )
+    .unwrap();
+
+    assert_eq!(extraction.items[0].content, "Great\n\nLoved it");
+    assert_eq!(extraction.items[1].content, "No title");
+    // Fields used by the template are not copied into metadata
+    assert_eq!(metadata_keys(&extraction), vec!["lang"]);
+}
+
+#[test]
+fn test_content_template_nested_paths_arrays_and_defaults() {
+    let data = vec![json!({
+        "caption": "Sunset",
+        "hashtags": ["beach", "summer"],
+        "owner": { "username": "jane" },
+        "images": [{ "alt": "A beach" }],
+        "timestamp": "2024-01-01"
+    })];
+    let key_mapping = mappings(json!([
+        { "from": "timestamp", "to": "date", "kind": { "Date": { "format": "%Y-%m-%d" } } }
+    ]));
+
+    let extraction = extract(
+        data,
+        &key_mapping,
+        &settings(json!({
+            "content_template": "{owner.username}: {caption} {hashtags} ({images[0].alt}) {{{location|unknown}}}"
+        })),
+    )
+    .unwrap();
+
+    assert_eq!(
+        extraction.items[0].content,
+        "jane: Sunset beach summer (A beach) {unknown}"
+    );
+}
+
+#[test]
+fn test_empty_content_template_falls_back_to_mapping() {
+    let key_mapping = mappings(json!([
+        { "from": "body", "to": "content", "kind": "String" },
+        { "from": "date", "to": "date", "kind": { "Date": { "format": "%Y-%m-%d" } } }
+    ]));
+    let data = vec![
+        json!({ "body": "from mapping", "date": "2024-01-01" }),
+        json!({ "date": "2024-01-01" }),
+    ];
+
+    let extraction = extract(
+        data,
+        &key_mapping,
+        &settings(json!({ "content_template": "{title}" })),
+    )
+    .unwrap();
+
+    assert_eq!(extraction.items[0].content, "from mapping");
+    assert_eq!(extraction.report.dropped, 1);
+}
+
+#[test]
+fn test_invalid_content_template_fails_extraction() {
+    let err = extract(
+        vec![],
+        &unidentified_mappings(),
+        &settings(json!({ "content_template": "{title" })),
+    )
+    .unwrap_err();
+
+    assert!(format!("{:#}", err).contains("Unclosed placeholder"));
+}
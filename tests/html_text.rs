@@ -0,0 +1,54 @@
+use apify_connector::html_text::html_to_text;
+
+#[test]
+fn test_strips_tags_and_collapses_whitespace() {
+    let html =
+        "<div class=\"review\">\n  <b>Great</b>   place,\n <a href=\"#\">would</a> return</div>";
+
+    assert_eq!(html_to_text(html, false), "Great place, would return");
+}
+
+#[test]
+fn test_drops_scripts_styles_and_comments() {
+    let html = "<style>p { color: red }</style><p>Visible</p><!-- hidden --><SCRIPT>alert('x')</script>text";
+
+    assert_eq!(html_to_text(html, false), "Visible text");
+}
+
+#[test]
+fn test_decodes_entities() {
+    let html = "Fish &amp; chips &lt;3 &#233;t&#xE9; &eacute;&nbsp;&hellip; &unknown; AT&T";
+
+    assert_eq!(
+        html_to_text(html, false),
+        "Fish & chips <3 été é … &unknown; AT&T"
+    );
+}
+
+#[test]
+fn test_escaped_markup_is_kept_as_text() {
+    assert_eq!(
+        html_to_text("&lt;p&gt;not a tag&lt;/p&gt;", false),
+        "<p>not a tag</p>"
+    );
+}
+
+#[test]
+fn test_keeps_paragraph_and_line_breaks() {
+    let html = "<h1>Title</h1><p>First line<br>second line</p>\n<p>  </p><ul><li>one</li><li>two</li></ul>";
+
+    assert_eq!(
+        html_to_text(html, true),
+        "Title\n\nFirst line\nsecond line\n\none\n\ntwo"
+    );
+    assert_eq!(
+        html_to_text(html, false),
+        "Title First line second line one two"
+    );
+}
+
+#[test]
+fn test_plain_text_and_stray_brackets() {
+    assert_eq!(html_to_text("just text", true), "just text");
+    assert_eq!(html_to_text("1 < 2 and 3 > 2", false), "1 < 2 and 3 > 2");
+}
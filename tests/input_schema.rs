@@ -0,0 +1,196 @@
+use apify_connector::actors::ActorConfig;
+use apify_connector::dto::BuildDetails;
+use apify_connector::handlers::handle_job;
+use apify_connector::input_schema::{InputSchema, SchemaErrors};
+use apify_connector::job::check_actor_input;
+use apify_connector::state_store::SharedStateStore;
+use axum::{http::StatusCode, routing::post, Router};
+use http_body_util::BodyExt;
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+fn pointers(errors: &SchemaErrors) -> Vec<&str> {
+    let mut pointers: Vec<&str> = errors
+        .violations
+        .iter()
+        .map(|v| v.pointer.as_str())
+        .collect();
+    pointers.sort();
+    pointers
+}
+
+#[test]
+fn test_typed_config_reports_every_violation() {
+    let error = ActorConfig::from_type_and_config(
+        "google_search",
+        json!({ "queries": ["rust", 42], "maxResults": -1, "language": null }),
+    )
+    .unwrap_err();
+
+    let errors = error.downcast_ref::<SchemaErrors>().unwrap();
+    assert_eq!(errors.target, "google_search config");
+    assert_eq!(
+        pointers(errors),
+        vec!["/language", "/maxResults", "/queries/1"]
+    );
+    assert!(error
+        .to_string()
+        .starts_with("Invalid google_search config: "));
+}
+
+#[test]
+fn test_missing_required_field_points_at_the_input() {
+    let error =
+        ActorConfig::from_type_and_config("instagram", json!({ "maxPosts": 5 })).unwrap_err();
+
+    let errors = error.downcast_ref::<SchemaErrors>().unwrap();
+    assert_eq!(pointers(errors), vec![""]);
+    assert!(errors.violations[0].message.contains("usernames"));
+}
+
+#[test]
+fn test_apify_input_schema_is_checked() {
+    let schema = InputSchema::compile(&json!({
+        "title": "Input",
+        "type": "object",
+        "schemaVersion": 1,
+        "properties": {
+            "startUrls": { "title": "Start URLs", "type": "array", "editor": "requestListSources" },
+            "maxItems": { "title": "Max items", "type": "integer", "minimum": 1 }
+        },
+        "required": ["startUrls"]
+    }))
+    .unwrap();
+
+    assert!(schema
+        .check("actor_input", &json!({ "startUrls": [] }))
+        .is_ok());
+
+    let errors = schema
+        .check("actor_input", &json!({ "startUrls": "x", "maxItems": 0 }))
+        .unwrap_err();
+    assert_eq!(pointers(&errors), vec!["/maxItems", "/startUrls"]);
+
+    assert!(InputSchema::compile(&json!({ "type": 12 })).is_err());
+}
+
+#[test]
+fn test_apify_extensions_are_understood() {
+    let schema = InputSchema::compile_apify(&json!({
+        "title": "Input",
+        "type": "object",
+        "schemaVersion": 1,
+        "properties": {
+            "startUrls": { "title": "Start URLs", "type": "array", "editor": "requestListSources" },
+            "maxItems": { "title": "Max items", "type": "integer", "nullable": true },
+            "proxy": { "title": "Proxy", "type": "string", "enum": ["auto", "none"], "nullable": true },
+            "pageFunction": { "title": "Page function", "type": "string", "default": "() => {}" }
+        },
+        "required": ["startUrls", "pageFunction"]
+    }))
+    .unwrap();
+
+    assert!(schema
+        .check(
+            "actor_input",
+            &json!({ "startUrls": [], "maxItems": null, "proxy": null })
+        )
+        .is_ok());
+
+    let errors = schema
+        .check(
+            "actor_input",
+            &json!({ "maxItems": "ten", "pageFunction": null }),
+        )
+        .unwrap_err();
+    assert_eq!(pointers(&errors), vec!["", "/maxItems", "/pageFunction"]);
+    assert!(errors
+        .violations
+        .iter()
+        .any(|v| v.pointer.is_empty() && v.message.contains("startUrls")));
+}
+
+#[tokio::test]
+async fn test_input_is_not_checked_when_the_schema_cannot_be_fetched() {
+    let body = [("startUrls".to_string(), json!("not a list"))].into();
+    assert!(
+        check_actor_input("connector-tests/no-such-actor", "token", &body)
+            .await
+            .is_ok()
+    );
+}
+
+#[test]
+fn test_build_input_schema_is_read_from_either_field() {
+    let encoded: BuildDetails = serde_json::from_value(json!({
+        "inputSchema": "{\"type\":\"object\",\"required\":[\"url\"]}"
+    }))
+    .unwrap();
+    assert_eq!(
+        encoded.schema().unwrap().unwrap()["required"],
+        json!(["url"])
+    );
+
+    let definition: BuildDetails = serde_json::from_value(json!({
+        "actorDefinition": { "input": { "type": "object" } }
+    }))
+    .unwrap();
+    assert_eq!(definition.schema().unwrap().unwrap()["type"], "object");
+
+    let both: BuildDetails = serde_json::from_value(json!({
+        "inputSchema": "{\"title\":\"encoded\"}",
+        "actorDefinition": { "input": { "title": "definition" } }
+    }))
+    .unwrap();
+    assert_eq!(both.schema().unwrap().unwrap()["title"], "encoded");
+
+    let invalid: BuildDetails = serde_json::from_value(json!({ "inputSchema": "{" })).unwrap();
+    assert!(invalid.schema().is_err());
+
+    let not_an_object: BuildDetails = serde_json::from_value(json!({
+        "actorDefinition": { "input": "INPUT_SCHEMA.json" }
+    }))
+    .unwrap();
+    assert!(not_an_object.schema().unwrap().is_none());
+
+    let none: BuildDetails = serde_json::from_value(json!({})).unwrap();
+    assert!(none.schema().unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_invalid_config_is_rejected_with_422() {
+    let app = Router::new()
+        .route("/{actor_type}", post(handle_job))
+        .with_state(SharedStateStore::None);
+    let job = json!({
+        "settings": {
+            "actor_config": { "startUrls": "https://example.com", "maxPages": "ten" },
+            "token": "token"
+        }
+    });
+
+    let response = app
+        .oneshot(
+            axum::http::Request::builder()
+                .method("POST")
+                .uri("/web_scraper")
+                .header("content-type", "application/json")
+                .body(axum::body::Body::from(job.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    let mut pointers: Vec<&str> = body["violations"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|v| v["pointer"].as_str().unwrap())
+        .collect();
+    pointers.sort();
+    assert_eq!(pointers, vec!["/maxPages", "/startUrls"]);
+    assert!(body.get("errors").is_none());
+}
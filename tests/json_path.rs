@@ -0,0 +1,73 @@
+use apify_connector::json_path::{get, get_or_insert, parse, Segment};
+use serde_json::json;
+
+#[test]
+fn test_parse_keys_and_indexes() {
+    assert_eq!(
+        parse("startUrls[0].userData.since").unwrap(),
+        vec![
+            Segment::Key("startUrls".into()),
+            Segment::Index(0),
+            Segment::Key("userData".into()),
+            Segment::Key("since".into()),
+        ]
+    );
+    assert_eq!(
+        parse("matrix[1][2]").unwrap(),
+        vec![
+            Segment::Key("matrix".into()),
+            Segment::Index(1),
+            Segment::Index(2)
+        ]
+    );
+}
+
+#[test]
+fn test_parse_rejects_malformed_paths() {
+    assert!(parse("a..b").is_err());
+    assert!(parse("").is_err());
+    assert!(parse("a[x]").is_err());
+    assert!(parse("a[0").is_err());
+}
+
+#[test]
+fn test_get_resolves_nested_values() {
+    let value = json!({ "author": { "name": "Jane" }, "tags": ["a", "b"] });
+
+    assert_eq!(get(&value, "author.name"), Some(&json!("Jane")));
+    assert_eq!(get(&value, "tags[1]"), Some(&json!("b")));
+    assert_eq!(get(&value, "tags[5]"), None);
+    assert_eq!(get(&value, "author.age"), None);
+    assert_eq!(get(&value, "author..name"), None);
+}
+
+#[test]
+fn test_get_or_insert_creates_missing_containers() {
+    let mut value = json!({ "startUrls": [{ "url": "https://example.com" }] });
+
+    *get_or_insert(&mut value, &parse("startUrls[0].userData.since").unwrap()).unwrap() =
+        json!("2024-01-01");
+    *get_or_insert(&mut value, &parse("startUrls[1].url").unwrap()).unwrap() =
+        json!("https://example.org");
+    *get_or_insert(&mut value, &parse("search.dateFrom").unwrap()).unwrap() = json!("today");
+
+    assert_eq!(
+        value,
+        json!({
+            "startUrls": [
+                { "url": "https://example.com", "userData": { "since": "2024-01-01" } },
+                { "url": "https://example.org" }
+            ],
+            "search": { "dateFrom": "today" }
+        })
+    );
+}
+
+#[test]
+fn test_get_or_insert_rejects_mismatched_containers() {
+    let mut value = json!({ "name": "x", "tags": ["a"] });
+
+    assert!(get_or_insert(&mut value, &parse("name.first").unwrap()).is_err());
+    assert!(get_or_insert(&mut value, &parse("tags[3]").unwrap()).is_err());
+    assert!(get_or_insert(&mut value, &parse("tags.first").unwrap()).is_err());
+}
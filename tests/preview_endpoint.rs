@@ -0,0 +1,109 @@
+use apify_connector::handlers::handle_preview;
+use axum::{http::StatusCode, routing::post, Router};
+use http_body_util::BodyExt;
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+fn app() -> Router {
+    Router::new().route("/preview", post(handle_preview))
+}
+
+async fn post_preview(body: Value) -> (StatusCode, Value) {
+    let response = app()
+        .oneshot(
+            axum::http::Request::builder()
+                .method("POST")
+                .uri("/preview")
+                .header("content-type", "application/json")
+                .body(axum::body::Body::from(body.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let status = response.status();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    (status, serde_json::from_slice(&body).unwrap())
+}
+
+#[tokio::test]
+async fn test_preview_extracts_items_and_updates_state() {
+    let (status, body) = post_preview(json!({
+        "items": [
+            { "text": "Great", "date": "2024-01-15", "url": "https://a" },
+            { "text": "No date", "url": "https://b" }
+        ],
+        "key_mapping": [
+            { "from": "text", "to": "content", "kind": "String" },
+            { "from": "date", "to": "date", "kind": { "Date": { "format": "%Y-%m-%d" } } },
+            { "from": "url", "to": "id", "kind": "String" }
+        ],
+        "state_mapping": [
+            { "from": "last_date", "to": "start_date", "update": "$format_date(start_date, \"%Y\")" }
+        ],
+        "state": "{\"last_date\": \"2000\"}"
+    }))
+    .await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["result"].as_array().unwrap().len(), 1);
+    assert_eq!(body["result"][0]["id"], "https://a");
+    assert_eq!(body["result"][0]["date"], "2024-01-15T00:00:00Z");
+    assert_eq!(body["report"]["seen"], 2);
+    assert_eq!(body["report"]["dropped"], 1);
+
+    let state: Value = serde_json::from_str(body["state"].as_str().unwrap()).unwrap();
+    let year = chrono::Utc::now().format("%Y").to_string();
+    assert_eq!(state["last_date"], year);
+}
+
+#[tokio::test]
+async fn test_preview_requires_items_or_dataset() {
+    let (status, body) = post_preview(json!({ "key_mapping": [] })).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body["error"].as_str().unwrap().contains("items"));
+}
+
+#[tokio::test]
+async fn test_preview_reports_mapping_errors_as_bad_request() {
+    let (status, body) = post_preview(json!({
+        "items": [{ "text": "x" }],
+        "key_mapping": [
+            { "from": "text", "to": "content", "kind": "String", "transform": "value +" },
+            { "from": "date", "to": "date", "kind": { "Date": { "format": "%Y-%m-%d" } } }
+        ]
+    }))
+    .await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["errors"][0]["field"], "key_mapping[0].transform");
+    assert!(body["errors"][0]["message"]
+        .as_str()
+        .unwrap()
+        .contains("Invalid expression 'value +'"));
+}
+
+#[tokio::test]
+async fn test_preview_accepts_and_returns_state_object() {
+    let (status, body) = post_preview(json!({
+        "items": [
+            { "text": "a", "date": "2024-01-15" },
+            { "text": "b", "date": "2024-01-20" }
+        ],
+        "key_mapping": [
+            { "from": "text", "to": "content", "kind": "String" },
+            { "from": "date", "to": "date", "kind": { "Date": { "format": "%Y-%m-%d" } } }
+        ],
+        "state_mapping": [
+            { "from": "total", "to": "total", "update": "$total + count(items)" }
+        ],
+        "state": { "total": 40, "tags": ["x"] }
+    }))
+    .await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["state_object"], json!({ "total": 42, "tags": ["x"] }));
+    let encoded: Value = serde_json::from_str(body["state"].as_str().unwrap()).unwrap();
+    assert_eq!(encoded, body["state_object"]);
+}
@@ -0,0 +1,266 @@
+use std::sync::Arc;
+
+use apify_connector::handlers::list_functions;
+use apify_connector::scripting::{compile, engine};
+use axum::Json;
+use chrono::{DateTime, Utc};
+use rhai::{Dynamic, Scope};
+
+fn eval(script: &str) -> Dynamic {
+    let mut scope = Scope::new();
+    let date: DateTime<Utc> = "2024-03-13T15:30:45Z".parse().unwrap();
+    scope.push("date", date);
+    engine()
+        .eval_with_scope::<Dynamic>(&mut scope, script)
+        .unwrap_or_else(|e| panic!("{}: {}", script, e))
+}
+
+fn eval_string(script: &str) -> String {
+    eval(script).into_string().unwrap()
+}
+
+fn eval_error(script: &str) -> String {
+    engine()
+        .eval::<Dynamic>(script)
+        .expect_err(script)
+        .to_string()
+}
+
+#[tokio::test]
+async fn test_functions_are_listed() {
+    let Json(functions) = list_functions().await;
+
+    let names: Vec<&str> = functions.iter().map(|f| f.name).collect();
+    for name in [
+        "parse_date",
+        "start_of_week",
+        "max_date",
+        "json_get",
+        "or_default",
+    ] {
+        assert!(names.contains(&name), "{} is not listed", name);
+    }
+    assert!(functions.iter().all(|f| f.signature.starts_with(f.name)));
+}
+
+#[test]
+fn test_now() {
+    let now = eval("now()").try_cast::<DateTime<Utc>>().unwrap();
+    assert!((Utc::now() - now).num_seconds() < 5);
+}
+
+#[test]
+fn test_parse_date() {
+    assert_eq!(
+        eval_string(r#"format_date(parse_date("2024-03-13", "%Y-%m-%d"), "%+")"#),
+        "2024-03-13T00:00:00+00:00"
+    );
+    assert_eq!(
+        eval_string(r#"format_date(parse_date("13/03/2024 10:05", "%d/%m/%Y %H:%M"), "%+")"#),
+        "2024-03-13T10:05:00+00:00"
+    );
+    assert_eq!(
+        eval_string(
+            r#"format_date(parse_date("2024-03-13 10:05 +0200", "%Y-%m-%d %H:%M %z"), "%+")"#
+        ),
+        "2024-03-13T08:05:00+00:00"
+    );
+    assert!(
+        eval_error(r#"parse_date("yesterday", "%Y-%m-%d")"#).contains("Cannot parse 'yesterday'")
+    );
+}
+
+#[test]
+fn test_format_date() {
+    assert_eq!(
+        eval_string(r#"format_date(date, "%Y-%m-%d")"#),
+        "2024-03-13"
+    );
+    assert!(eval(r#"format_date((), "%Y")"#).is_unit());
+}
+
+#[test]
+fn test_add_and_sub_durations() {
+    assert_eq!(
+        eval_string(r#"format_date(add_days(date, 20), "%F")"#),
+        "2024-04-02"
+    );
+    assert_eq!(
+        eval_string(r#"format_date(sub_days(date, 13), "%F")"#),
+        "2024-02-29"
+    );
+    assert_eq!(
+        eval_string(r#"format_date(add_hours(date, 9), "%F %T")"#),
+        "2024-03-14 00:30:45"
+    );
+    assert_eq!(
+        eval_string(r#"format_date(add_minutes(date, -31), "%T")"#),
+        "14:59:45"
+    );
+    assert!(eval("add_days((), 1)").is_unit());
+}
+
+#[test]
+fn test_out_of_range_dates_are_errors() {
+    for script in [
+        "add_days(now(), 9223372036854775807)",
+        "sub_days(now(), 9223372036854775807)",
+        "add_days(now(), 100000000)",
+        "add_hours(now(), -9223372036854775807)",
+        "add_minutes(now(), 9223372036854775807)",
+    ] {
+        assert!(eval_error(script).contains("out of range"), "{}", script);
+    }
+    assert!(eval_error("start_of_week(from_unix(-8334601228800))").contains("The week of"));
+}
+
+#[test]
+fn test_invalid_date_format_is_an_error() {
+    assert!(eval_error(r#"format_date(now(), "%Q")"#).contains("Invalid date format '%Q'"));
+}
+
+#[test]
+fn test_start_of_periods() {
+    assert_eq!(
+        eval_string(r#"format_date(start_of_day(date), "%F %T")"#),
+        "2024-03-13 00:00:00"
+    );
+    // 2024-03-13 is a Wednesday
+    assert_eq!(
+        eval_string(r#"format_date(start_of_week(date), "%F %T")"#),
+        "2024-03-11 00:00:00"
+    );
+    assert_eq!(
+        eval_string(r#"format_date(start_of_month(date), "%F %T")"#),
+        "2024-03-01 00:00:00"
+    );
+    assert!(eval("start_of_week(())").is_unit());
+}
+
+#[test]
+fn test_unix_timestamps() {
+    assert_eq!(eval("to_unix(date)").as_int().unwrap(), 1710343845);
+    assert_eq!(
+        eval_string(r#"format_date(from_unix(86400), "%F")"#),
+        "1970-01-02"
+    );
+    assert!(eval_error("from_unix(9223372036854775807)").contains("out of range"));
+}
+
+#[test]
+fn test_min_max_dates() {
+    assert_eq!(
+        eval_string(r#"format_date(max(date, add_days(date, 1)), "%F")"#),
+        "2024-03-14"
+    );
+    assert_eq!(
+        eval_string(r#"format_date(min(date, add_days(date, 1)), "%F")"#),
+        "2024-03-13"
+    );
+    assert_eq!(
+        eval_string(r#"format_date(max((), date), "%F")"#),
+        "2024-03-13"
+    );
+    assert_eq!(eval("max(3, 7)").as_int().unwrap(), 7);
+}
+
+#[test]
+fn test_item_aggregates() {
+    let items = r#"[#{ id: "a", date: date }, #{ id: (), date: add_days(date, 2) }]"#;
+    assert_eq!(
+        eval_string(&format!(r#"format_date(max_date({}), "%F")"#, items)),
+        "2024-03-15"
+    );
+    assert_eq!(
+        eval_string(&format!(r#"format_date(min_date({}), "%F")"#, items)),
+        "2024-03-13"
+    );
+    assert_eq!(eval(&format!("count({})", items)).as_int().unwrap(), 2);
+    assert_eq!(eval_string(&format!("last_id({})", items)), "a");
+    assert!(eval("max_date([])").is_unit());
+}
+
+#[test]
+fn test_regex_helpers() {
+    assert!(eval(r#"regex_match("order 42", "\\d+")"#)
+        .as_bool()
+        .unwrap());
+    assert_eq!(
+        eval_string(r#"regex_extract("order 42", "order (\\d+)")"#),
+        "42"
+    );
+    assert!(eval(r#"regex_extract("none", "\\d+")"#).is_unit());
+    assert!(eval_error(r#"regex_match("x", "(")"#).contains("Invalid regex"));
+}
+
+#[test]
+fn test_regex_cache_stays_correct_past_its_size() {
+    // More patterns than the cache holds, each used twice
+    let script = r#"
+        let matched = 0;
+        for i in 0..300 {
+            let pattern = "^n" + i + "$";
+            if regex_match("n" + i, pattern) && regex_extract("n" + i, pattern) == "n" + i {
+                matched += 1;
+            }
+        }
+        matched
+    "#;
+    assert_eq!(eval(script).as_int().unwrap(), 300);
+    assert!(eval_error(r#"regex_match("x", "(")"#).contains("Invalid regex"));
+}
+
+#[test]
+fn test_string_helpers() {
+    assert_eq!(
+        eval_string(r#"strip_html("<p>Fish &amp; <b>chips</b></p>")"#),
+        "Fish & chips"
+    );
+    assert_eq!(
+        eval_string(r#"slugify("  Hello, World! 2024 ")"#),
+        "hello-world-2024"
+    );
+    assert_eq!(eval_string(r#"left("héllo", 2)"#), "hé");
+    assert_eq!(eval_string(r#"left("abc", -1)"#), "");
+    assert_eq!(eval_string(r#"or_default("", "fallback")"#), "fallback");
+    assert_eq!(eval_string(r#"or_default((), "fallback")"#), "fallback");
+    assert_eq!(eval_string(r#"or_default("value", "fallback")"#), "value");
+}
+
+#[test]
+fn test_json_get() {
+    let state = r#"#{ cursor: #{ pages: ["p1", "p2"], since: "2024-01-01" } }"#;
+    assert_eq!(
+        eval_string(&format!(r#"json_get({}, "cursor.since")"#, state)),
+        "2024-01-01"
+    );
+    assert_eq!(
+        eval_string(&format!(r#"json_get({}, "cursor.pages[1]")"#, state)),
+        "p2"
+    );
+    assert!(eval(&format!(r#"json_get({}, "cursor.missing")"#, state)).is_unit());
+    assert!(eval_error(r#"json_get(#{}, "a..b")"#).contains("Invalid path"));
+}
+
+#[test]
+fn test_runaway_scripts_are_aborted() {
+    assert!(eval_error("loop { }").contains("Too many operations"));
+    assert!(eval_error(r#"let s = "x"; loop { s += s; }"#).contains("Length of string"));
+    assert!(eval_error("fn f(n) { f(n + 1) } f(0)").contains("Stack overflow"));
+}
+
+#[test]
+fn test_unsafe_features_are_disabled() {
+    assert!(compile(r#"import "std" as std; 1"#).is_err());
+    assert!(compile(r#"eval("1 + 1")"#).is_err());
+}
+
+#[test]
+fn test_compiled_scripts_are_cached() {
+    let first = compile("items.len() + 1").unwrap();
+    let second = compile("items.len() + 1").unwrap();
+    assert!(Arc::ptr_eq(&first, &second));
+
+    let error = compile("value +").unwrap_err().to_string();
+    assert!(error.contains("Invalid expression 'value +'"));
+}
@@ -0,0 +1,352 @@
+use std::collections::HashMap;
+
+use apify_connector::dto::{ExportItem, JobState, MergeStrategy, StateMapping, StateUpdate};
+use apify_connector::extraction::SEEN_DIGESTS_KEY;
+use apify_connector::mapping_utils::{apply_state_mapping, update_state_core, Context};
+use chrono::{TimeZone, Utc};
+use serde_json::{json, Value};
+
+fn item(id: Option<&str>, day: u32) -> ExportItem {
+    ExportItem {
+        id: id.map(String::from),
+        content: format!("item {}", day),
+        date: Utc.with_ymd_and_hms(2024, 1, day, 12, 0, 0).unwrap(),
+        metadata: HashMap::new(),
+    }
+}
+
+fn mapping(from: &str, update: &str) -> StateMapping {
+    StateMapping {
+        from: from.into(),
+        to: Some(from.into()),
+        update: update.into(),
+        merge: MergeStrategy::Replace,
+    }
+}
+
+fn state(value: Value) -> JobState {
+    serde_json::from_value(value).unwrap()
+}
+
+fn update(
+    items: &[ExportItem],
+    previous: Value,
+    mapping: Vec<StateMapping>,
+) -> anyhow::Result<Value> {
+    let state = update_state_core(items, &state(previous), Some(&mapping), Context::new())?;
+    Ok(serde_json::to_value(state)?)
+}
+
+#[test]
+fn test_seen_digests_are_stored_in_state() {
+    let ctx = Context::new().with_seen_digests(Some(vec!["abc".into(), "def".into()]));
+
+    let state = update_state_core(&[], &state(json!({ "cursor": "x" })), None, ctx).unwrap();
+
+    assert_eq!(state[SEEN_DIGESTS_KEY], json!(["abc", "def"]));
+    assert_eq!(state["cursor"], "x");
+}
+
+#[test]
+fn test_state_untouched_without_seen_digests() {
+    let previous = state(json!({ "cursor": "x" }));
+
+    let state = update_state_core(&[], &previous, None, Context::new()).unwrap();
+
+    assert_eq!(state, previous);
+}
+
+#[test]
+fn test_state_mapping_errors_are_reported() {
+    let err = update(
+        &[],
+        json!({}),
+        vec![mapping("cursor", "$unknown_fn(start_date)")],
+    )
+    .unwrap_err();
+
+    let message = err.to_string();
+    assert!(message.contains("Failed to evaluate state mapping for 'cursor'"));
+    assert!(message.contains("unknown_fn"));
+}
+
+#[test]
+fn test_state_derived_from_items() {
+    let items = vec![item(Some("a"), 5), item(Some("b"), 12), item(None, 3)];
+    let mapping = vec![
+        mapping("since", "$format_date(max_date(items), \"%Y-%m-%d\")"),
+        mapping("oldest", "$format_date(min_date(items), \"%Y-%m-%d\")"),
+        mapping("last_id", "$last_id(items)"),
+        mapping("count", "$count(items)"),
+    ];
+
+    let state = update(&items, json!({}), mapping).unwrap();
+
+    assert_eq!(
+        state,
+        json!({ "since": "2024-01-12", "oldest": "2024-01-03", "last_id": "b", "count": 3 })
+    );
+}
+
+#[test]
+fn test_items_fields_are_readable() {
+    let items = vec![item(Some("a"), 5)];
+    let state = update(
+        &items,
+        json!({}),
+        vec![mapping("first", "$items[0].content")],
+    )
+    .unwrap();
+
+    assert_eq!(state["first"], "item 5");
+}
+
+#[test]
+fn test_empty_run_keeps_previous_cursor() {
+    let mapping = vec![
+        mapping("since", "$format_date(max_date(items), \"%Y-%m-%d\")"),
+        mapping("last_id", "$last_id(items)"),
+    ];
+
+    let state = update(
+        &[],
+        json!({ "since": "2024-01-01", "last_id": "z" }),
+        mapping,
+    )
+    .unwrap();
+
+    assert_eq!(state, json!({ "since": "2024-01-01", "last_id": "z" }));
+}
+
+#[test]
+fn test_state_mapping_keeps_json_types() {
+    let items = vec![item(Some("a"), 5), item(Some("b"), 12)];
+    let mapping = vec![
+        mapping("runs", "$runs + 1"),
+        mapping("ratio", "$0.5"),
+        mapping("ids", "$items.map(|item| item.id)"),
+        mapping(
+            "cursor",
+            "$#{ page: cursor.page + 1, done: count(items) == 0 }",
+        ),
+        mapping("latest", "$max_date(items)"),
+    ];
+
+    let state = update(
+        &items,
+        json!({ "runs": 41, "cursor": { "page": 1 } }),
+        mapping,
+    )
+    .unwrap();
+
+    assert_eq!(
+        state,
+        json!({
+            "runs": 42,
+            "ratio": 0.5,
+            "ids": ["a", "b"],
+            "cursor": { "page": 2, "done": false },
+            "latest": "2024-01-12T12:00:00+00:00"
+        })
+    );
+}
+
+#[test]
+fn test_previous_state_values_are_variables() {
+    let mapping = vec![
+        mapping(
+            "since",
+            "$format_date(add_days(parse_date(since, \"%Y-%m-%d\"), 1), \"%Y-%m-%d\")",
+        ),
+        mapping("page", "$json_get(state, \"cursor.next\")"),
+    ];
+
+    let state = update(
+        &[],
+        json!({ "since": "2024-01-31", "cursor": { "next": "p2" } }),
+        mapping,
+    )
+    .unwrap();
+
+    assert_eq!(state["since"], "2024-02-01");
+    assert_eq!(state["page"], "p2");
+}
+
+fn parse_mappings(mappings: Value) -> Vec<StateMapping> {
+    serde_json::from_value(mappings).unwrap()
+}
+
+#[test]
+fn test_state_update_forms_are_parsed() {
+    let mappings = parse_mappings(json!([
+        { "from": "a", "to": "a", "update": { "literal": { "page": 1 } } },
+        { "from": "b", "to": "b", "update": { "expr": "runs + 1" } },
+        { "from": "c", "to": "c", "update": { "template": "{start_date:%Y-%m-%d}" } },
+        { "from": "d", "to": "d", "update": { "keep": true } },
+        { "from": "e", "to": "e", "update": "$runs + 1" },
+        { "from": "f", "to": "f", "update": "2024-01-01" }
+    ]));
+
+    let updates: Vec<StateUpdate> = mappings.into_iter().map(|m| m.update).collect();
+    assert_eq!(
+        updates,
+        vec![
+            StateUpdate::Literal(json!({ "page": 1 })),
+            StateUpdate::Expr("runs + 1".into()),
+            StateUpdate::Template("{start_date:%Y-%m-%d}".into()),
+            StateUpdate::Keep,
+            StateUpdate::Expr("runs + 1".into()),
+            StateUpdate::Literal(json!("2024-01-01")),
+        ]
+    );
+}
+
+#[test]
+fn test_invalid_state_update_forms_are_rejected() {
+    for update in [
+        json!({ "keep": false }),
+        json!({ "script": "1" }),
+        json!(42),
+    ] {
+        let mapping = json!([{ "from": "a", "to": "a", "update": update }]);
+        let error = serde_json::from_value::<Vec<StateMapping>>(mapping).unwrap_err();
+        assert!(
+            error.to_string().contains("Invalid state update"),
+            "{}",
+            error
+        );
+    }
+}
+
+#[test]
+fn test_every_update_mode_is_applied() {
+    let mappings = parse_mappings(json!([
+        { "from": "cursor", "to": "cursor", "update": { "literal": { "page": 1 } } },
+        { "from": "runs", "to": "runs", "update": { "expr": "runs + 1" } },
+        { "from": "since", "to": "since", "update": { "template": "{start_date:%Y-%m-%d}" } },
+        { "from": "label", "to": "label", "update": { "template": "run {runs} from {since}{missing}" } },
+        { "from": "token", "to": "token", "update": { "keep": true } },
+        { "from": "mode", "to": "mode", "update": "incremental" }
+    ]));
+    let ctx = Context {
+        start: Utc.with_ymd_and_hms(2024, 3, 13, 15, 30, 0).unwrap(),
+        seen_digests: None,
+    };
+
+    let previous = state(json!({ "runs": 1, "since": "2024-01-01", "token": "t" }));
+    let state = update_state_core(&[], &previous, Some(&mappings), ctx).unwrap();
+
+    assert_eq!(
+        serde_json::to_value(state).unwrap(),
+        json!({
+            "cursor": { "page": 1 },
+            "runs": 2,
+            "since": "2024-03-13",
+            "label": "run 1 from 2024-01-01",
+            "token": "t",
+            "mode": "incremental"
+        })
+    );
+}
+
+#[test]
+fn test_template_formats_only_dates() {
+    let mappings = || {
+        parse_mappings(json!([
+            { "from": "since", "to": "since", "update": { "template": "{since:%d/%m}" } }
+        ]))
+    };
+
+    let error = update(&[], json!({ "since": "2024-01-01" }), mappings()).unwrap_err();
+    assert!(error
+        .to_string()
+        .contains("'since' is not an RFC 3339 date"));
+
+    let state = update(&[], json!({ "since": "2024-01-05T00:00:00Z" }), mappings()).unwrap();
+    assert_eq!(state["since"], "05/01");
+}
+
+fn body(value: Value) -> HashMap<String, Value> {
+    serde_json::from_value(value).unwrap()
+}
+
+#[test]
+fn test_state_is_placed_at_nested_paths() {
+    let mappings = parse_mappings(json!([
+        { "from": "since", "to": "search.dateFrom", "update": "$start_date" },
+        { "from": "since", "to": "startUrls[0].userData.since", "update": "$start_date" },
+        { "from": "page", "to": "page", "update": "$page + 1" },
+        { "from": "missing", "to": "other.value", "update": "$1" }
+    ]));
+    let mut input = body(json!({
+        "search": { "query": "rust" },
+        "startUrls": [{ "url": "https://example.com" }]
+    }));
+
+    let state = state(json!({ "since": "2024-01-01", "page": 3 }));
+    apply_state_mapping(&mut input, &state, Some(&mappings)).unwrap();
+
+    assert_eq!(
+        serde_json::to_value(input).unwrap(),
+        json!({
+            "search": { "query": "rust", "dateFrom": "2024-01-01" },
+            "startUrls": [{ "url": "https://example.com", "userData": { "since": "2024-01-01" } }],
+            "page": 3
+        })
+    );
+}
+
+#[test]
+fn test_state_merge_strategies() {
+    let mappings = parse_mappings(json!([
+        { "from": "urls", "to": "startUrls", "update": "$urls", "merge": "Append" },
+        { "from": "url", "to": "startUrls", "update": "$url", "merge": "Append" },
+        { "from": "tags", "to": "tags", "update": "$tags", "merge": "Append" },
+        { "from": "options", "to": "options", "update": "$options", "merge": "DeepMerge" },
+        { "from": "proxy", "to": "proxy", "update": "$proxy", "merge": "Replace" }
+    ]));
+    let mut input = body(json!({
+        "startUrls": ["a"],
+        "options": { "limit": 10, "filters": { "lang": "en", "country": "fr" } },
+        "proxy": { "useApifyProxy": true, "groups": ["RESIDENTIAL"] }
+    }));
+
+    let state = state(json!({
+        "urls": ["b", "c"],
+        "url": "d",
+        "tags": "new",
+        "options": { "filters": { "country": "de" }, "since": "2024-01-01" },
+        "proxy": { "useApifyProxy": false }
+    }));
+    apply_state_mapping(&mut input, &state, Some(&mappings)).unwrap();
+
+    assert_eq!(
+        serde_json::to_value(input).unwrap(),
+        json!({
+            "startUrls": ["a", "b", "c", "d"],
+            "tags": ["new"],
+            "options": {
+                "limit": 10,
+                "filters": { "lang": "en", "country": "de" },
+                "since": "2024-01-01"
+            },
+            "proxy": { "useApifyProxy": false }
+        })
+    );
+}
+
+#[test]
+fn test_append_requires_an_array_target() {
+    let mappings = parse_mappings(json!([
+        { "from": "url", "to": "startUrl", "update": "$url", "merge": "Append" }
+    ]));
+    let mut input = body(json!({ "startUrl": "a" }));
+
+    let state = state(json!({ "url": "b" }));
+    let error = apply_state_mapping(&mut input, &state, Some(&mappings)).unwrap_err();
+
+    assert!(error
+        .to_string()
+        .contains("Cannot place state 'url' at 'startUrl'"));
+    assert_eq!(input["startUrl"], "a");
+}
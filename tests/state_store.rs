@@ -0,0 +1,247 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use apify_connector::dto::JobState;
+use apify_connector::handlers::{delete_state, get_state, handle_arbitrary_actor, put_state};
+use apify_connector::state_store::{SharedStateStore, StateStore, StoreError};
+use axum::{
+    http::{Method, StatusCode},
+    routing::{get, post},
+    Router,
+};
+use http_body_util::BodyExt;
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("apify-connector-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+fn state(value: Value) -> JobState {
+    serde_json::from_value(value).unwrap()
+}
+
+fn app(store: SharedStateStore) -> Router {
+    Router::new()
+        .route(
+            "/state/{job_name}",
+            get(get_state).put(put_state).delete(delete_state),
+        )
+        .route("/run", post(handle_arbitrary_actor))
+        .with_state(store)
+}
+
+async fn send(app: Router, method: Method, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+    let request = axum::http::Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("content-type", "application/json")
+        .body(axum::body::Body::from(
+            body.map(|b| b.to_string()).unwrap_or_default(),
+        ))
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+
+    let status = response.status();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body = serde_json::from_slice(&body).unwrap_or(Value::Null);
+    (status, body)
+}
+
+#[test]
+fn test_put_increments_version() {
+    let store = StateStore::open(temp_dir("versions")).unwrap();
+    assert!(store.get("reviews").unwrap().is_none());
+
+    let first = store
+        .put("reviews", state(json!({ "since": "a" })), None)
+        .unwrap();
+    let second = store
+        .put("reviews", state(json!({ "since": "b" })), Some(1))
+        .unwrap();
+
+    assert_eq!((first.version, second.version), (1, 2));
+    let stored = store.get("reviews").unwrap().unwrap();
+    assert_eq!(stored.version, 2);
+    assert_eq!(stored.state, state(json!({ "since": "b" })));
+}
+
+#[test]
+fn test_put_rejects_stale_version() {
+    let store = StateStore::open(temp_dir("conflict")).unwrap();
+    store.put("reviews", state(json!({})), Some(0)).unwrap();
+
+    let err = store
+        .put("reviews", state(json!({ "x": 1 })), Some(0))
+        .unwrap_err();
+
+    assert!(matches!(
+        err,
+        StoreError::Conflict {
+            expected: 0,
+            actual: 1,
+            ..
+        }
+    ));
+    assert_eq!(
+        store.get("reviews").unwrap().unwrap().state,
+        state(json!({}))
+    );
+}
+
+#[test]
+fn test_writes_leave_no_temporary_files() {
+    let dir = temp_dir("atomic");
+    let store = StateStore::open(&dir).unwrap();
+    store.put("a", state(json!({ "n": 1 })), None).unwrap();
+    store.put("a", state(json!({ "n": 2 })), None).unwrap();
+
+    let files: Vec<_> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .filter(|name| name != ".lock")
+        .collect();
+    assert_eq!(files, vec!["a.json"]);
+    // The store survives a restart
+    let reopened = StateStore::open(&dir).unwrap();
+    assert_eq!(reopened.get("a").unwrap().unwrap().version, 2);
+}
+
+#[test]
+fn test_stores_sharing_a_directory_never_lose_a_write() {
+    let dir = temp_dir("replicas");
+    // Each thread has its own store, as replicas sharing DATA_DIR would
+    let handles: Vec<_> = (0..4)
+        .map(|_| {
+            let store = StateStore::open(&dir).unwrap();
+            std::thread::spawn(move || {
+                let mut written = 0;
+                while written < 10 {
+                    let version = store.get("shared").unwrap().map_or(0, |s| s.version);
+                    match store.put("shared", state(json!({ "n": version })), Some(version)) {
+                        Ok(_) => written += 1,
+                        Err(StoreError::Conflict { .. }) => continue,
+                        Err(e) => panic!("{}", e),
+                    }
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    let store = StateStore::open(&dir).unwrap();
+    assert_eq!(store.get("shared").unwrap().unwrap().version, 40);
+}
+
+#[test]
+fn test_delete_checks_version() {
+    let store = StateStore::open(temp_dir("delete")).unwrap();
+    store.put("a", state(json!({})), None).unwrap();
+
+    assert!(matches!(
+        store.delete("a", Some(5)),
+        Err(StoreError::Conflict { .. })
+    ));
+    assert!(store.delete("a", Some(1)).unwrap());
+    assert!(!store.delete("a", None).unwrap());
+}
+
+#[test]
+fn test_job_names_cannot_escape_the_directory() {
+    let store = StateStore::open(temp_dir("names")).unwrap();
+
+    for name in ["", "../etc/passwd", ".hidden", "a/b", "a b"] {
+        assert!(
+            matches!(store.get(name), Err(StoreError::InvalidJobName(_))),
+            "{:?} should be rejected",
+            name
+        );
+    }
+    assert!(store.get("reviews-2024_v1.daily").is_ok());
+}
+
+#[tokio::test]
+async fn test_state_endpoints() {
+    let store = Some(Arc::new(StateStore::open(temp_dir("endpoints")).unwrap()));
+
+    let (status, _) = send(app(store.clone()), Method::GET, "/state/reviews", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, body) = send(
+        app(store.clone()),
+        Method::PUT,
+        "/state/reviews",
+        Some(json!({ "state": { "since": "2024-01-01" }, "version": 0 })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["version"], 1);
+
+    let (status, body) = send(
+        app(store.clone()),
+        Method::PUT,
+        "/state/reviews",
+        Some(json!({ "state": {}, "version": 0 })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert!(body["error"].as_str().unwrap().contains("version 1"));
+
+    let (status, body) = send(app(store.clone()), Method::GET, "/state/reviews", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["job_name"], "reviews");
+    assert_eq!(body["state"], json!({ "since": "2024-01-01" }));
+
+    let (status, _) = send(
+        app(store.clone()),
+        Method::DELETE,
+        "/state/reviews?version=2",
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    let (status, _) = send(
+        app(store.clone()),
+        Method::DELETE,
+        "/state/reviews?version=1",
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = send(app(store), Method::DELETE, "/state/reviews", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_state_endpoints_when_disabled() {
+    let (status, body) = send(app(None), Method::GET, "/state/reviews", None).await;
+
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert!(body["error"].as_str().unwrap().contains("DATA_DIR"));
+}
+
+#[tokio::test]
+async fn test_named_job_requires_state_store() {
+    let (status, body) = send(
+        app(None),
+        Method::POST,
+        "/run",
+        Some(json!({
+            "settings": {
+                "actor_id": "apify/web-scraper",
+                "actor_input": {},
+                "token": "token",
+                "key_mapping": []
+            },
+            "job_name": "reviews"
+        })),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["errors"][0]["field"], "job_name");
+}
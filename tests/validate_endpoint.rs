@@ -0,0 +1,150 @@
+use apify_connector::handlers::handle_validate;
+use axum::{http::StatusCode, routing::post, Router};
+use http_body_util::BodyExt;
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+fn app() -> Router {
+    Router::new().route("/validate", post(handle_validate))
+}
+
+async fn post_validate(body: Value) -> (StatusCode, Value) {
+    let response = app()
+        .oneshot(
+            axum::http::Request::builder()
+                .method("POST")
+                .uri("/validate")
+                .header("content-type", "application/json")
+                .body(axum::body::Body::from(body.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let status = response.status();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    (status, serde_json::from_slice(&body).unwrap())
+}
+
+fn fields(body: &Value) -> Vec<&str> {
+    body["errors"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| e["field"].as_str().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_validate_accepts_valid_mappings() {
+    let (status, body) = post_validate(json!({
+        "key_mapping": [
+            { "from": "text", "to": "content", "kind": "String" },
+            { "from": "date", "to": "date", "kind": { "Date": { "format": "%Y-%m-%d" } }, "default": "now" }
+        ],
+        "state_mapping": [
+            { "from": "last_date", "to": "start_date", "update": "$format_date(start_date, \"%Y\")" }
+        ],
+        "filter": "item.text != \"\""
+    }))
+    .await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, json!({ "valid": true }));
+}
+
+#[tokio::test]
+async fn test_validate_requires_content_and_date_targets() {
+    let (status, body) = post_validate(json!({
+        "key_mapping": [{ "from": "url", "to": "id", "kind": "String" }]
+    }))
+    .await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(fields(&body), vec!["key_mapping", "key_mapping"]);
+    assert!(body["errors"][0]["message"].as_str().unwrap().contains("'content'"));
+    assert!(body["errors"][1]["message"].as_str().unwrap().contains("'date'"));
+}
+
+#[tokio::test]
+async fn test_validate_accepts_content_template_instead_of_content_mapping() {
+    let (status, _) = post_validate(json!({
+        "key_mapping": [
+            { "from": "date", "to": "date", "kind": { "Date": { "format": "%Y-%m-%d" } } }
+        ],
+        "content_template": "{title}: {text}"
+    }))
+    .await;
+
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_validate_checks_kind_compatibility() {
+    let (status, body) = post_validate(json!({
+        "key_mapping": [
+            { "from": "text", "to": "content", "kind": "String" },
+            { "from": "date", "to": "date", "kind": "String" },
+            { "from": "published", "to": "published", "kind": { "Date": { "format": "%Q" } } },
+            { "from": [], "to": "author", "kind": "String" }
+        ]
+    }))
+    .await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(
+        fields(&body),
+        vec!["key_mapping[1].kind", "key_mapping[2].kind", "key_mapping[3].from"]
+    );
+}
+
+#[tokio::test]
+async fn test_validate_reports_every_script_error() {
+    let (status, body) = post_validate(json!({
+        "key_mapping": [
+            { "from": "text", "to": "content", "kind": "String", "transform": "value +" },
+            { "from": "date", "to": "date", "kind": { "Date": { "format": "%Y-%m-%d" } } }
+        ],
+        "state_mapping": [
+            { "from": "cursor", "to": "since", "update": "$format_date(start_date" },
+            { "from": "label", "to": "label", "update": { "template": "{start_date:%Q}" } },
+            { "from": "page", "to": "search..page", "update": { "expr": "page +" } }
+        ],
+        "filter": "item.rating >"
+    }))
+    .await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(
+        fields(&body),
+        vec![
+            "key_mapping[0].transform",
+            "state_mapping[0].update",
+            "state_mapping[1].update",
+            "state_mapping[2].to",
+            "state_mapping[2].update",
+            "filter"
+        ]
+    );
+    assert!(body["error"].as_str().unwrap().starts_with("Invalid request"));
+}
+
+#[tokio::test]
+async fn test_validate_dry_runs_state_mapping() {
+    let (status, body) = post_validate(json!({
+        "key_mapping": [
+            { "from": "text", "to": "content", "kind": "String" },
+            { "from": "date", "to": "date", "kind": { "Date": { "format": "%Y-%m-%d" } } }
+        ],
+        "state_mapping": [
+            { "from": "cursor", "to": "since", "update": "$unknown_fn(start_date)" }
+        ]
+    }))
+    .await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body["error"]
+        .as_str()
+        .unwrap()
+        .contains("Failed to evaluate state mapping for 'cursor'"));
+}